@@ -0,0 +1,183 @@
+use core::fmt;
+
+/// Logical address of a `DoIP` entity, ECU or tester, as carried in the two
+/// byte address fields of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LogicalAddress(u16);
+
+/// Ranges of logical addresses defined by ISO 13400-2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressClass {
+    /// Reserved by ISO/SAE, `0x0000`, `0x8000..=0xDFFF` and `0xF000..=0xFFFF`.
+    IsoSaeReserved,
+
+    /// Vehicle manufacturer specific ECU, `0x0001..=0x0DFF` and
+    /// `0x1000..=0x7FFF`.
+    VmSpecific,
+
+    /// External legacy test equipment, `0x0E00..=0x0E7F`.
+    ExternalLegacyTester,
+
+    /// External `DoIP` test equipment, `0x0E80..=0x0EFF`.
+    ExternalDoipTester,
+
+    /// Internal data collection and on-board diagnostic equipment,
+    /// `0x0F00..=0x0FFF`.
+    InternalTester,
+
+    /// Functional group address defined by ISO/SAE use case standards,
+    /// `0xE000..=0xE3FF`.
+    Functional,
+
+    /// Vehicle manufacturer specific functional group address,
+    /// `0xE400..=0xEFFF`.
+    OemSpecific,
+}
+
+impl LogicalAddress {
+    /// Creates a logical address from its numeric value.
+    #[must_use]
+    pub const fn new(address: u16) -> Self {
+        LogicalAddress(address)
+    }
+
+    /// Returns the numeric value of the address.
+    #[must_use]
+    pub const fn value(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the address as carried in a payload.
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+
+    /// Returns the range of ISO 13400-2 the address lies in.
+    #[must_use]
+    pub const fn class(self) -> AddressClass {
+        match self.0 {
+            0x0001..=0x0DFF | 0x1000..=0x7FFF => AddressClass::VmSpecific,
+            0x0E00..=0x0E7F => AddressClass::ExternalLegacyTester,
+            0x0E80..=0x0EFF => AddressClass::ExternalDoipTester,
+            0x0F00..=0x0FFF => AddressClass::InternalTester,
+            0xE000..=0xE3FF => AddressClass::Functional,
+            0xE400..=0xEFFF => AddressClass::OemSpecific,
+            _ => AddressClass::IsoSaeReserved,
+        }
+    }
+
+    /// Returns `true` if the address belongs to test equipment.
+    #[must_use]
+    pub const fn is_tester(self) -> bool {
+        matches!(
+            self.class(),
+            AddressClass::ExternalLegacyTester
+                | AddressClass::ExternalDoipTester
+                | AddressClass::InternalTester
+        )
+    }
+
+    /// Returns `true` if the address is a functional group address.
+    #[must_use]
+    pub const fn is_functional(self) -> bool {
+        matches!(
+            self.class(),
+            AddressClass::Functional | AddressClass::OemSpecific
+        )
+    }
+}
+
+impl From<u16> for LogicalAddress {
+    fn from(value: u16) -> Self {
+        LogicalAddress(value)
+    }
+}
+
+impl From<[u8; 2]> for LogicalAddress {
+    fn from(value: [u8; 2]) -> Self {
+        LogicalAddress(u16::from_be_bytes(value))
+    }
+}
+
+impl From<LogicalAddress> for u16 {
+    fn from(value: LogicalAddress) -> Self {
+        value.0
+    }
+}
+
+impl From<LogicalAddress> for [u8; 2] {
+    fn from(value: LogicalAddress) -> Self {
+        value.to_bytes()
+    }
+}
+
+impl fmt::Display for AddressClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AddressClass::IsoSaeReserved => "ISO/SAE reserved",
+            AddressClass::VmSpecific => "VM-specific ECU",
+            AddressClass::ExternalLegacyTester => "external legacy tester",
+            AddressClass::ExternalDoipTester => "external DoIP tester",
+            AddressClass::InternalTester => "internal tester",
+            AddressClass::Functional => "functional group",
+            AddressClass::OemSpecific => "VM-specific functional group",
+        })
+    }
+}
+
+impl fmt::Display for LogicalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06X}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AddressClass, LogicalAddress};
+
+    #[test]
+    fn test_logical_address_conversions() {
+        let address = LogicalAddress::from([0x0e, 0x80]);
+
+        assert_eq!(address, LogicalAddress::new(0x0e80));
+        assert_eq!(u16::from(address), 0x0e80);
+        assert_eq!(<[u8; 2]>::from(address), [0x0e, 0x80]);
+        assert_eq!(address.to_string(), "0x0E80");
+    }
+
+    #[test]
+    fn test_logical_address_class() {
+        let cases = [
+            (0x0000, AddressClass::IsoSaeReserved),
+            (0x0001, AddressClass::VmSpecific),
+            (0x0dff, AddressClass::VmSpecific),
+            (0x0e00, AddressClass::ExternalLegacyTester),
+            (0x0e7f, AddressClass::ExternalLegacyTester),
+            (0x0e80, AddressClass::ExternalDoipTester),
+            (0x0eff, AddressClass::ExternalDoipTester),
+            (0x0f00, AddressClass::InternalTester),
+            (0x1000, AddressClass::VmSpecific),
+            (0x7fff, AddressClass::VmSpecific),
+            (0x8000, AddressClass::IsoSaeReserved),
+            (0xdfff, AddressClass::IsoSaeReserved),
+            (0xe000, AddressClass::Functional),
+            (0xe400, AddressClass::OemSpecific),
+            (0xefff, AddressClass::OemSpecific),
+            (0xf000, AddressClass::IsoSaeReserved),
+        ];
+
+        for (address, class) in cases {
+            assert_eq!(
+                LogicalAddress::new(address).class(),
+                class,
+                "{address:#06x}"
+            );
+        }
+
+        assert!(LogicalAddress::new(0x0e80).is_tester());
+        assert!(!LogicalAddress::new(0x1000).is_tester());
+        assert!(LogicalAddress::new(0xe400).is_functional());
+        assert!(!LogicalAddress::new(0x0e80).is_functional());
+    }
+}
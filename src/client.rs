@@ -0,0 +1,1031 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use doip_definitions::{
+    header::ProtocolVersion,
+    message::DoipMessage,
+    payload::{
+        ActivationCode, ActivationType, AliveCheckResponse, DiagnosticMessage, DiagnosticNackCode,
+        DoipPayload, NackCode, RoutingActivationRequest,
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+    time::{timeout_at, Instant},
+};
+
+#[cfg(feature = "tls")]
+use crate::TlsClientConfig;
+use crate::{
+    DecodeError, DoipCodec, DoipFrame, EncodeError, ExtendedMessage, LogicalAddress, RecoveryPolicy,
+};
+
+/// Time a tester waits for the response to a control message such as a
+/// routing activation request, `A_DoIP_Ctrl` of ISO 13400-2.
+pub const A_DOIP_CTRL: Duration = Duration::from_secs(2);
+
+/// Time a tester waits for the acknowledgement of a diagnostic message,
+/// `A_DoIP_Diagnostic_Message` of ISO 13400-2.
+pub const A_DOIP_DIAGNOSTIC_MESSAGE: Duration = Duration::from_secs(2);
+
+/// Negative response code with which an ECU announces that its response is
+/// still pending.
+const NRC_RESPONSE_PENDING: u8 = 0x78;
+
+/// Number of received messages buffered until the client picks them up,
+/// further messages are dropped. Also bounds the messages kept for
+/// [`DoipClient::take_unhandled_messages`].
+const INCOMING_CAPACITY: usize = 32;
+
+/// The timer which ran out while waiting for a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// `A_DoIP_Ctrl`, waiting for the routing activation response.
+    Ctrl,
+
+    /// `A_DoIP_Diagnostic_Message`, waiting for the diagnostic message
+    /// acknowledgement.
+    DiagnosticMessage,
+
+    /// Waiting for the diagnostic response of the ECU.
+    Response,
+}
+
+impl fmt::Display for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Timer::Ctrl => "A_DoIP_Ctrl",
+            Timer::DiagnosticMessage => "A_DoIP_Diagnostic_Message",
+            Timer::Response => "diagnostic response",
+        })
+    }
+}
+
+/// Errors raised by a [`DoipClient`] and by vehicle discovery.
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    /// IO error from the connection
+    #[error("Underlying I/O Error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    /// message received from the entity failed to decode
+    #[error("failed to decode message: {0}")]
+    Decode(#[from] DecodeError),
+
+    /// message failed to encode
+    #[error("failed to encode message: {0}")]
+    Encode(#[from] EncodeError),
+
+    /// timer ran out before the expected message was received
+    #[error("timed out waiting for {0}")]
+    Timeout(Timer),
+
+    /// entity denied the routing activation
+    #[error("routing activation denied: {0:?}")]
+    RoutingActivationDenied(ActivationCode),
+
+    /// entity rejected a diagnostic message
+    #[error("diagnostic message rejected: {0:?}")]
+    DiagnosticNack(DiagnosticNackCode),
+
+    /// entity replied with a generic NACK
+    #[error("generic nack received: {0:?}")]
+    GenericNack(NackCode),
+
+    /// entity closed the connection
+    #[error("connection closed")]
+    ConnectionClosed,
+}
+
+/// Configuration of a [`DoipClient`].
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    source_address: LogicalAddress,
+    activation_type: ActivationType,
+    protocol_version: ProtocolVersion,
+    ctrl_timeout: Duration,
+    diagnostic_message_timeout: Duration,
+    response_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsClientConfig>,
+}
+
+impl ClientConfig {
+    /// Creates a configuration for a tester with the given logical address,
+    /// using the default routing activation and the timing of ISO 13400-2.
+    #[must_use]
+    pub fn new(source_address: LogicalAddress) -> Self {
+        ClientConfig {
+            source_address,
+            activation_type: ActivationType::Default,
+            protocol_version: ProtocolVersion::Iso13400_2012,
+            ctrl_timeout: A_DOIP_CTRL,
+            diagnostic_message_timeout: A_DOIP_DIAGNOSTIC_MESSAGE,
+            response_timeout: Duration::from_secs(5),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Sets the routing activation type requested.
+    #[must_use]
+    pub fn with_activation_type(mut self, activation_type: ActivationType) -> Self {
+        self.activation_type = activation_type;
+        self
+    }
+
+    /// Sets the protocol version of outgoing messages.
+    #[must_use]
+    pub fn with_protocol_version(mut self, protocol_version: ProtocolVersion) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// Sets how long to wait for the routing activation response.
+    #[must_use]
+    pub fn with_ctrl_timeout(mut self, ctrl_timeout: Duration) -> Self {
+        self.ctrl_timeout = ctrl_timeout;
+        self
+    }
+
+    /// Sets how long to wait for the acknowledgement of a diagnostic message.
+    #[must_use]
+    pub fn with_diagnostic_message_timeout(mut self, diagnostic_message_timeout: Duration) -> Self {
+        self.diagnostic_message_timeout = diagnostic_message_timeout;
+        self
+    }
+
+    /// Sets how long to wait for the diagnostic response of an ECU, restarted
+    /// whenever the ECU reports its response as pending.
+    #[must_use]
+    pub fn with_response_timeout(mut self, response_timeout: Duration) -> Self {
+        self.response_timeout = response_timeout;
+        self
+    }
+
+    /// Enables reconnecting over TLS when the entity denies routing activation
+    /// on an unencrypted connection with
+    /// `ActivationCode::DeniedRequestEncryptedTLSConnection`.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn with_tls(mut self, tls: TlsClientConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Returns the logical address of the tester.
+    #[must_use]
+    pub fn source_address(&self) -> LogicalAddress {
+        self.source_address
+    }
+
+    /// Returns the routing activation type requested.
+    #[must_use]
+    pub fn activation_type(&self) -> ActivationType {
+        self.activation_type
+    }
+
+    /// Returns the protocol version of outgoing messages.
+    #[must_use]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Returns how long to wait for the routing activation response.
+    #[must_use]
+    pub fn ctrl_timeout(&self) -> Duration {
+        self.ctrl_timeout
+    }
+
+    /// Returns how long to wait for the acknowledgement of a diagnostic
+    /// message.
+    #[must_use]
+    pub fn diagnostic_message_timeout(&self) -> Duration {
+        self.diagnostic_message_timeout
+    }
+
+    /// Returns how long to wait for the diagnostic response of an ECU.
+    #[must_use]
+    pub fn response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    /// Returns the TLS configuration used to reconnect, if enabled.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls(&self) -> Option<&TlsClientConfig> {
+        self.tls.as_ref()
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new(LogicalAddress::new(0x0e80))
+    }
+}
+
+/// Write half of a connection, shared with the task answering alive checks.
+struct Writer {
+    io: Box<dyn AsyncWrite + Send + Unpin>,
+    codec: DoipCodec,
+    buf: BytesMut,
+}
+
+impl Writer {
+    async fn send(&mut self, message: DoipMessage) -> Result<(), ClientError> {
+        tokio_util::codec::Encoder::encode(&mut self.codec, message, &mut self.buf)?;
+        self.io.write_all_buf(&mut self.buf).await?;
+        self.io.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// A tester connected to a `DoIP` entity over TCP.
+///
+/// Connecting performs the routing activation. Alive check requests of the
+/// entity are answered in the background for as long as the client exists,
+/// even while received messages are not picked up. Messages arriving while
+/// the buffer of the client is full are dropped and counted.
+///
+/// Messages received while waiting for something else, such as a diagnostic
+/// response arriving before its acknowledgement, are kept. Diagnostic
+/// responses among them are returned by [`DoipClient::recv_diagnostic`], all
+/// others by [`DoipClient::take_unhandled_messages`].
+pub struct DoipClient {
+    config: ClientConfig,
+    entity_address: LogicalAddress,
+    writer: Arc<Mutex<Writer>>,
+    incoming: mpsc::Receiver<Result<DoipMessage, ClientError>>,
+    unhandled: VecDeque<DoipMessage>,
+    dropped: Arc<AtomicUsize>,
+    reader: JoinHandle<()>,
+}
+
+impl DoipClient {
+    /// Connects to a `DoIP` entity and activates routing.
+    ///
+    /// If TLS is configured and the entity requests an encrypted connection,
+    /// the client reconnects to the TLS port of the entity and activates
+    /// routing there.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClientError::RoutingActivationDenied` if the entity denied the
+    /// routing activation, `ClientError::Timeout` if it did not respond in
+    /// time, and any other `ClientError` if the connection failed.
+    pub async fn connect<A: ToSocketAddrs>(
+        addr: A,
+        config: ClientConfig,
+    ) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = config.tls.clone() {
+            let peer_addr = stream.peer_addr()?;
+
+            return match Self::from_stream(stream, config.clone()).await {
+                Err(ClientError::RoutingActivationDenied(
+                    ActivationCode::DeniedRequestEncryptedTLSConnection,
+                )) => Self::connect_tls((peer_addr.ip(), tls.port()), &tls, config).await,
+                res => res,
+            };
+        }
+
+        Self::from_stream(stream, config).await
+    }
+
+    /// Activates routing on an established connection, such as a TLS stream.
+    ///
+    /// # Errors
+    ///
+    /// See [`DoipClient::connect`].
+    pub async fn from_stream<S>(stream: S, config: ClientConfig) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read, write) = tokio::io::split(stream);
+        let writer = Arc::new(Mutex::new(Writer {
+            io: Box::new(write),
+            codec: DoipCodec::new(),
+            buf: BytesMut::new(),
+        }));
+        let alive_check_response = DoipCodec::build_message(
+            config.protocol_version,
+            DoipPayload::AliveCheckResponse(AliveCheckResponse {
+                source_address: config.source_address.to_bytes(),
+            }),
+        )?;
+        let (tx, incoming) = mpsc::channel(INCOMING_CAPACITY);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let reader = tokio::spawn(read_messages(
+            read,
+            Arc::clone(&writer),
+            tx,
+            Arc::clone(&dropped),
+            alive_check_response,
+        ));
+
+        let mut client = DoipClient {
+            config,
+            entity_address: LogicalAddress::new(0),
+            writer,
+            incoming,
+            unhandled: VecDeque::new(),
+            dropped,
+            reader,
+        };
+        client.entity_address = client.activate_routing().await?;
+
+        Ok(client)
+    }
+
+    /// Returns the configuration of the client.
+    #[must_use]
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Returns the logical address of the entity, as reported in its routing
+    /// activation response.
+    #[must_use]
+    pub fn entity_address(&self) -> LogicalAddress {
+        self.entity_address
+    }
+
+    /// Returns the number of messages dropped because they arrived while the
+    /// buffer of received messages was full.
+    #[must_use]
+    pub fn dropped_messages(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the messages received while waiting for something else, oldest
+    /// first. Only the most recent ones are kept, older ones count as dropped.
+    pub fn take_unhandled_messages(&mut self) -> Vec<DoipMessage> {
+        self.unhandled.drain(..).collect()
+    }
+
+    /// Sends a diagnostic request to an ECU and returns its response.
+    ///
+    /// # Errors
+    ///
+    /// See [`DoipClient::send_diagnostic`] and
+    /// [`DoipClient::recv_diagnostic`].
+    pub async fn diagnostic_request(
+        &mut self,
+        target_address: LogicalAddress,
+        data: &[u8],
+    ) -> Result<Vec<u8>, ClientError> {
+        self.send_diagnostic(target_address, data).await?;
+        self.recv_diagnostic(target_address).await
+    }
+
+    /// Sends diagnostic data to an ECU and waits for the entity to acknowledge
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClientError::DiagnosticNack` if the entity rejected the
+    /// message, `ClientError::Timeout` if it was not acknowledged within
+    /// `A_DoIP_Diagnostic_Message`, and any other `ClientError` if the
+    /// connection failed.
+    pub async fn send_diagnostic(
+        &mut self,
+        target_address: LogicalAddress,
+        data: &[u8],
+    ) -> Result<(), ClientError> {
+        let source_address = self.config.source_address.to_bytes();
+        let target = target_address.to_bytes();
+
+        self.send(DoipPayload::DiagnosticMessage(DiagnosticMessage {
+            source_address,
+            target_address: target,
+            message: data.to_vec(),
+        }))
+        .await?;
+
+        // Acknowledgements kept from earlier requests are stale, only the ones
+        // received from now on are considered
+        let timeout = self.config.diagnostic_message_timeout;
+        self.wait_for(Timer::DiagnosticMessage, timeout, |payload| match payload {
+            DoipPayload::DiagnosticMessageAck(ack)
+                if ack.source_address == target && ack.target_address == source_address =>
+            {
+                Some(Ok(()))
+            }
+            DoipPayload::DiagnosticMessageNack(nack)
+                if nack.source_address == target && nack.target_address == source_address =>
+            {
+                Some(Err(ClientError::DiagnosticNack(nack.nack_code)))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    /// Waits for the diagnostic response of an ECU, starting with responses
+    /// received while waiting for something else.
+    ///
+    /// Responses announcing that the final response is pending, negative
+    /// responses with code `0x78`, restart the timer and are not returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClientError::Timeout` if no response was received in time, and
+    /// any other `ClientError` if the connection failed.
+    pub async fn recv_diagnostic(
+        &mut self,
+        source_address: LogicalAddress,
+    ) -> Result<Vec<u8>, ClientError> {
+        let source = source_address.to_bytes();
+        let target = self.config.source_address.to_bytes();
+
+        let mut filter = |payload: &DoipPayload| match payload {
+            DoipPayload::DiagnosticMessage(msg)
+                if msg.source_address == source && msg.target_address == target =>
+            {
+                Some(Ok(msg.message.clone()))
+            }
+            _ => None,
+        };
+
+        loop {
+            let data = if let Some(data) = self.take_unhandled(&mut filter) {
+                data?
+            } else {
+                let timeout = self.config.response_timeout;
+                self.wait_for(Timer::Response, timeout, &mut filter).await?
+            };
+
+            if !matches!(data.as_slice(), [0x7f, _, NRC_RESPONSE_PENDING]) {
+                return Ok(data);
+            }
+        }
+    }
+
+    /// Requests routing activation, returning the logical address of the
+    /// entity.
+    ///
+    /// A response requiring confirmation is followed by the final response of
+    /// the entity, which has to arrive within the same `A_DoIP_Ctrl`.
+    async fn activate_routing(&mut self) -> Result<LogicalAddress, ClientError> {
+        let source_address = self.config.source_address.to_bytes();
+
+        self.send(DoipPayload::RoutingActivationRequest(
+            RoutingActivationRequest {
+                source_address,
+                activation_type: self.config.activation_type,
+                buffer: [0; 4],
+            },
+        ))
+        .await?;
+
+        let timeout = self.config.ctrl_timeout;
+        self.wait_for(Timer::Ctrl, timeout, |payload| match payload {
+            DoipPayload::RoutingActivationResponse(res)
+                if res.logical_address == source_address =>
+            {
+                match res.activation_code {
+                    ActivationCode::SuccessfullyActivated => {
+                        Some(Ok(LogicalAddress::from(res.source_address)))
+                    }
+                    ActivationCode::ActivatedConfirmationRequired => None,
+                    code => Some(Err(ClientError::RoutingActivationDenied(code))),
+                }
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    async fn send(&mut self, payload: DoipPayload) -> Result<(), ClientError> {
+        let message = DoipCodec::build_message(self.config.protocol_version, payload)?;
+
+        self.writer.lock().await.send(message).await
+    }
+
+    /// Waits until `filter` returns a result for a received payload, failing on
+    /// a generic NACK or once `timeout` has passed. Messages the filter does not
+    /// care about are kept as unhandled.
+    async fn wait_for<T>(
+        &mut self,
+        timer: Timer,
+        timeout: Duration,
+        mut filter: impl FnMut(&DoipPayload) -> Option<Result<T, ClientError>>,
+    ) -> Result<T, ClientError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let message = timeout_at(deadline, self.incoming.recv())
+                .await
+                .map_err(|_| ClientError::Timeout(timer))?
+                .unwrap_or(Err(ClientError::ConnectionClosed))?;
+
+            if let DoipPayload::GenericNack(nack) = message.payload {
+                return Err(ClientError::GenericNack(nack.nack_code));
+            }
+
+            if let Some(res) = filter(&message.payload) {
+                return res;
+            }
+
+            if self.unhandled.len() == INCOMING_CAPACITY {
+                self.unhandled.pop_front();
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            self.unhandled.push_back(message);
+        }
+    }
+
+    /// Removes the oldest unhandled message `filter` returns a result for.
+    fn take_unhandled<T>(
+        &mut self,
+        mut filter: impl FnMut(&DoipPayload) -> Option<Result<T, ClientError>>,
+    ) -> Option<Result<T, ClientError>> {
+        let (index, res) = self
+            .unhandled
+            .iter()
+            .enumerate()
+            .find_map(|(index, message)| Some((index, filter(&message.payload)?)))?;
+        self.unhandled.remove(index);
+
+        Some(res)
+    }
+}
+
+impl fmt::Debug for DoipClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoipClient")
+            .field("config", &self.config)
+            .field("entity_address", &self.entity_address)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for DoipClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Reads messages from the entity until the connection fails, answering alive
+/// check requests and forwarding everything else to the client.
+///
+/// Messages are forwarded without waiting for the client, which may not pick
+/// them up for a while, so that alive checks are still answered in time.
+/// Messages which fail to decode or hold unknown values are skipped.
+async fn read_messages<R: AsyncRead + Unpin>(
+    mut read: R,
+    writer: Arc<Mutex<Writer>>,
+    tx: mpsc::Sender<Result<DoipMessage, ClientError>>,
+    dropped: Arc<AtomicUsize>,
+    alive_check_response: DoipMessage,
+) {
+    // A malformed or unknown message from the entity is no reason to drop the
+    // connection, just like on the server side
+    let mut codec = DoipCodec::new()
+        .with_recovery_policy(RecoveryPolicy::SkipFrame)
+        .with_lenient_decoding(true);
+    let mut buf = BytesMut::new();
+
+    loop {
+        let res = match tokio_util::codec::Decoder::decode(&mut codec, &mut buf) {
+            // Optional fields, e.g. the request echoed in a diagnostic ACK, are
+            // not needed by the client
+            Ok(Some(
+                DoipFrame::Message(message) | DoipFrame::Extended(ExtendedMessage { message, .. }),
+            )) => {
+                if let DoipPayload::AliveCheckRequest(_) = message.payload {
+                    let res = writer.lock().await.send(alive_check_response.clone()).await;
+
+                    match res {
+                        Ok(()) => continue,
+                        Err(err) => Err(err),
+                    }
+                } else {
+                    Ok(message)
+                }
+            }
+            Ok(None) => match read.read_buf(&mut buf).await {
+                Ok(0) => Err(ClientError::ConnectionClosed),
+                Ok(_) => continue,
+                Err(err) => Err(err.into()),
+            },
+            Err(DecodeError::IOError(err)) => Err(err.into()),
+            // Raw frames and malformed messages are not of interest
+            Ok(Some(_)) | Err(_) => continue,
+        };
+
+        let message = match res {
+            Ok(message) => message,
+            Err(err) => {
+                // Waits for room, the connection is done with anyway
+                let _ = tx.send(Err(err)).await;
+                return;
+            }
+        };
+
+        match tx.try_send(Ok(message)) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use doip_definitions::payload::{
+        ActivationCode, AliveCheckRequest, DiagnosticAckCode, DiagnosticMessage,
+        DiagnosticMessageAck, DiagnosticMessageNack, DiagnosticNackCode, DoipPayload,
+        RoutingActivationResponse,
+    };
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    use crate::{
+        test_util::{extended, message},
+        ClientConfig, ClientError, DoipClient, DoipCodec, DoipFrame, LogicalAddress,
+        PayloadExtension, Timer,
+    };
+
+    use super::INCOMING_CAPACITY;
+
+    const TESTER: [u8; 2] = [0x0e, 0x80];
+    const ENTITY: [u8; 2] = [0x10, 0x00];
+    const ECU: [u8; 2] = [0x10, 0x01];
+
+    async fn recv(entity: &mut Framed<TcpStream, DoipCodec>) -> DoipPayload {
+        match entity.next().await.unwrap().unwrap() {
+            DoipFrame::Message(message) => message.payload,
+            frame => panic!("Unexpected frame {frame:?}"),
+        }
+    }
+
+    async fn activate(
+        listener: &TcpListener,
+        activation_code: ActivationCode,
+    ) -> Framed<TcpStream, DoipCodec> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut entity = Framed::new(stream, DoipCodec::new());
+
+        let DoipPayload::RoutingActivationRequest(req) = recv(&mut entity).await else {
+            panic!("Expected a routing activation request");
+        };
+        assert_eq!(req.source_address, TESTER);
+
+        entity
+            .send(message(DoipPayload::RoutingActivationResponse(
+                RoutingActivationResponse {
+                    logical_address: TESTER,
+                    source_address: ENTITY,
+                    activation_code,
+                    buffer: [0; 4],
+                },
+            )))
+            .await
+            .unwrap();
+
+        entity
+    }
+
+    #[tokio::test]
+    async fn test_client_diagnostic_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let mut entity = activate(&listener, ActivationCode::SuccessfullyActivated).await;
+
+            let DoipPayload::DiagnosticMessage(req) = recv(&mut entity).await else {
+                panic!("Expected a diagnostic message");
+            };
+            assert_eq!(req.message, [0x3e, 0x00]);
+
+            entity
+                .send(message(DoipPayload::AliveCheckRequest(
+                    AliveCheckRequest {},
+                )))
+                .await
+                .unwrap();
+            let DoipPayload::AliveCheckResponse(res) = recv(&mut entity).await else {
+                panic!("Expected an alive check response");
+            };
+            assert_eq!(res.source_address, TESTER);
+
+            let replies = [
+                DoipPayload::DiagnosticMessageAck(DiagnosticMessageAck {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    ack_code: DiagnosticAckCode::Acknowledged,
+                }),
+                DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    message: vec![0x7f, 0x3e, 0x78],
+                }),
+                DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    message: vec![0x7e, 0x00],
+                }),
+            ];
+            for reply in replies {
+                entity.send(message(reply)).await.unwrap();
+            }
+
+            entity
+        });
+
+        let mut client = DoipClient::connect(addr, ClientConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(client.entity_address(), LogicalAddress::from(ENTITY));
+
+        let res = client
+            .diagnostic_request(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await
+            .unwrap();
+        assert_eq!(res, [0x7e, 0x00]);
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_extended_replies() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut entity = Framed::new(stream, DoipCodec::new());
+
+            recv(&mut entity).await;
+            entity
+                .send(extended(
+                    DoipPayload::RoutingActivationResponse(RoutingActivationResponse {
+                        logical_address: TESTER,
+                        source_address: ENTITY,
+                        activation_code: ActivationCode::SuccessfullyActivated,
+                        buffer: [0; 4],
+                    }),
+                    PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+                ))
+                .await
+                .unwrap();
+
+            recv(&mut entity).await;
+            entity
+                .send(extended(
+                    DoipPayload::DiagnosticMessageAck(DiagnosticMessageAck {
+                        source_address: ECU,
+                        target_address: TESTER,
+                        ack_code: DiagnosticAckCode::Acknowledged,
+                    }),
+                    PayloadExtension::PreviousDiagnosticMessage(vec![0x3e, 0x00]),
+                ))
+                .await
+                .unwrap();
+
+            entity
+        });
+
+        let config =
+            ClientConfig::default().with_diagnostic_message_timeout(Duration::from_millis(200));
+        let mut client = DoipClient::connect(addr, config).await.unwrap();
+        assert_eq!(client.entity_address(), LogicalAddress::from(ENTITY));
+
+        let res = client
+            .send_diagnostic(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await;
+        assert!(res.is_ok());
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_activation_denied() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            activate(&listener, ActivationCode::DeniedUnknownSourceAddress).await
+        });
+
+        let res = DoipClient::connect(addr, ClientConfig::default()).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::RoutingActivationDenied(
+                ActivationCode::DeniedUnknownSourceAddress
+            ))
+        ));
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_diagnostic_nack_and_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let mut entity = activate(&listener, ActivationCode::SuccessfullyActivated).await;
+
+            recv(&mut entity).await;
+            entity
+                .send(message(DoipPayload::DiagnosticMessageNack(
+                    DiagnosticMessageNack {
+                        source_address: ECU,
+                        target_address: TESTER,
+                        nack_code: DiagnosticNackCode::UnknownTargetAddress,
+                    },
+                )))
+                .await
+                .unwrap();
+
+            recv(&mut entity).await;
+            entity
+        });
+
+        let config =
+            ClientConfig::default().with_diagnostic_message_timeout(Duration::from_millis(50));
+        let mut client = DoipClient::connect(addr, config).await.unwrap();
+
+        let res = client
+            .send_diagnostic(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await;
+        assert!(matches!(
+            res,
+            Err(ClientError::DiagnosticNack(
+                DiagnosticNackCode::UnknownTargetAddress
+            ))
+        ));
+
+        let res = client
+            .send_diagnostic(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await;
+        assert!(matches!(
+            res,
+            Err(ClientError::Timeout(Timer::DiagnosticMessage))
+        ));
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_response_before_ack() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let mut entity = activate(&listener, ActivationCode::SuccessfullyActivated).await;
+
+            recv(&mut entity).await;
+            let replies = [
+                DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    message: vec![0x7e, 0x00],
+                }),
+                DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                    source_address: [0x10, 0x02],
+                    target_address: TESTER,
+                    message: vec![0x50, 0x01],
+                }),
+                DoipPayload::DiagnosticMessageAck(DiagnosticMessageAck {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    ack_code: DiagnosticAckCode::Acknowledged,
+                }),
+            ];
+            for reply in replies {
+                entity.send(message(reply)).await.unwrap();
+            }
+
+            entity
+        });
+
+        let config = ClientConfig::default().with_response_timeout(Duration::from_millis(50));
+        let mut client = DoipClient::connect(addr, config).await.unwrap();
+
+        let res = client
+            .diagnostic_request(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await
+            .unwrap();
+        assert_eq!(res, [0x7e, 0x00]);
+
+        let unhandled = client.take_unhandled_messages();
+        assert_eq!(unhandled.len(), 1);
+        let DoipPayload::DiagnosticMessage(msg) = &unhandled[0].payload else {
+            panic!("Expected a diagnostic message");
+        };
+        assert_eq!(msg.source_address, [0x10, 0x02]);
+        assert!(client.take_unhandled_messages().is_empty());
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_skips_malformed_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let mut entity = activate(&listener, ActivationCode::SuccessfullyActivated).await;
+
+            recv(&mut entity).await;
+            // A diagnostic ACK too short to hold its addresses, followed by a
+            // routing activation response with an unknown activation code
+            let malformed = [
+                0x02, 0xfd, 0x80, 0x02, 0x00, 0x00, 0x00, 0x02, 0x10, 0x01, 0x02, 0xfd, 0x00, 0x06,
+                0x00, 0x00, 0x00, 0x09, 0x0e, 0x80, 0x10, 0x00, 0x42, 0x00, 0x00, 0x00, 0x00,
+            ];
+            tokio::io::AsyncWriteExt::write_all(entity.get_mut(), &malformed)
+                .await
+                .unwrap();
+            entity
+                .send(message(DoipPayload::DiagnosticMessageAck(
+                    DiagnosticMessageAck {
+                        source_address: ECU,
+                        target_address: TESTER,
+                        ack_code: DiagnosticAckCode::Acknowledged,
+                    },
+                )))
+                .await
+                .unwrap();
+
+            entity
+        });
+
+        let config =
+            ClientConfig::default().with_diagnostic_message_timeout(Duration::from_millis(200));
+        let mut client = DoipClient::connect(addr, config).await.unwrap();
+
+        let res = client
+            .send_diagnostic(LogicalAddress::from(ECU), &[0x3e, 0x00])
+            .await;
+        assert!(res.is_ok());
+
+        drop(entity.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_client_full_buffer() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let entity = tokio::spawn(async move {
+            let mut entity = activate(&listener, ActivationCode::SuccessfullyActivated).await;
+
+            for _ in 0..INCOMING_CAPACITY + 8 {
+                let res = DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                    source_address: ECU,
+                    target_address: TESTER,
+                    message: vec![0x7e, 0x00],
+                });
+                entity.feed(message(res)).await.unwrap();
+            }
+            let req = DoipPayload::AliveCheckRequest(AliveCheckRequest {});
+            entity.send(message(req)).await.unwrap();
+
+            let DoipPayload::AliveCheckResponse(res) = recv(&mut entity).await else {
+                panic!("Expected an alive check response");
+            };
+            assert_eq!(res.source_address, TESTER);
+
+            entity
+        });
+
+        let mut client = DoipClient::connect(addr, ClientConfig::default())
+            .await
+            .unwrap();
+
+        let entity = tokio::time::timeout(Duration::from_secs(1), entity).await;
+        drop(entity.unwrap().unwrap());
+
+        let mut queued = 0;
+        while client.incoming.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert!(client.dropped_messages() >= 8);
+        assert_eq!(queued + client.dropped_messages(), INCOMING_CAPACITY + 8);
+    }
+}
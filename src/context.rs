@@ -0,0 +1,366 @@
+use core::fmt;
+
+use doip_definitions::definitions::{DOIP_LENGTH_OFFSET, DOIP_TYPE_LEN, DOIP_TYPE_OFFSET};
+
+use crate::{DecodeError, DoipCodec, DoipFrame};
+
+#[cfg(feature = "std")]
+use crate::{EncodeError, Encoder};
+#[cfg(feature = "std")]
+use bytes::BytesMut;
+
+/// Details on where and why a message failed to decode.
+///
+/// Offsets are counted from the start of the message, header included. Fields
+/// are `None` if they do not apply to the error or the bytes have not been
+/// received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeErrorContext {
+    /// Payload type code announced in the header.
+    pub payload_type: Option<u16>,
+
+    /// Offset of the offending field.
+    pub offset: Option<usize>,
+
+    /// Raw value of the offending field, fields of several bytes read in
+    /// network byte order.
+    pub value: Option<u32>,
+
+    /// Payload length expected for the payload type, or the limit which has
+    /// been exceeded.
+    pub expected_length: Option<usize>,
+
+    /// Payload length announced in the header.
+    pub actual_length: Option<usize>,
+}
+
+impl DecodeErrorContext {
+    /// Creates the context of an error in the message whose header starts
+    /// `src`, holding the payload type.
+    pub(crate) fn of_header(src: &[u8]) -> Self {
+        let payload_type = src
+            .get(DOIP_TYPE_OFFSET..DOIP_TYPE_OFFSET + DOIP_TYPE_LEN)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+        DecodeErrorContext {
+            payload_type,
+            ..DecodeErrorContext::default()
+        }
+    }
+
+    /// Adds the field of `len` bytes at `offset` of `src` as the offending
+    /// field. The value is left out if the field has not been received or is
+    /// wider than a `u32`.
+    pub(crate) fn with_field(self, src: &[u8], offset: usize, len: usize) -> Self {
+        let value = src
+            .get(offset..offset + len)
+            .filter(|bytes| bytes.len() <= core::mem::size_of::<u32>())
+            .map(|bytes| {
+                bytes
+                    .iter()
+                    .fold(0, |value, &byte| value << 8 | u32::from(byte))
+            });
+
+        DecodeErrorContext {
+            offset: Some(offset),
+            value,
+            ..self
+        }
+    }
+
+    /// Adds the payload length field as the offending field, together with
+    /// the expected length or exceeded limit.
+    pub(crate) fn with_length(self, expected_length: Option<usize>, payload_length: u32) -> Self {
+        DecodeErrorContext {
+            offset: Some(DOIP_LENGTH_OFFSET),
+            expected_length,
+            actual_length: usize::try_from(payload_length).ok(),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for DecodeErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        let mut field = |f: &mut fmt::Formatter<'_>, args: fmt::Arguments<'_>| {
+            let res = write!(f, "{separator}{args}");
+            separator = ", ";
+            res
+        };
+
+        if let Some(payload_type) = self.payload_type {
+            field(f, format_args!("payload type {payload_type:#06x}"))?;
+        }
+        if let Some(offset) = self.offset {
+            field(f, format_args!("offset {offset}"))?;
+        }
+        if let Some(value) = self.value {
+            field(f, format_args!("value {value:#04x}"))?;
+        }
+        if let Some(expected_length) = self.expected_length {
+            field(f, format_args!("expected length {expected_length}"))?;
+        }
+        if let Some(actual_length) = self.actual_length {
+            field(f, format_args!("actual length {actual_length}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A `DecodeError` together with the context it occurred in.
+///
+/// The kind of error is still available as a plain [`DecodeError`] for
+/// matching.
+#[derive(thiserror::Error, Debug)]
+#[error("{kind} ({context})")]
+pub struct DetailedDecodeError {
+    kind: DecodeError,
+    context: DecodeErrorContext,
+}
+
+impl DetailedDecodeError {
+    /// Returns the kind of error.
+    #[must_use]
+    pub fn kind(&self) -> &DecodeError {
+        &self.kind
+    }
+
+    /// Returns the context the error occurred in.
+    #[must_use]
+    pub fn context(&self) -> &DecodeErrorContext {
+        &self.context
+    }
+
+    /// Drops the context, returning the kind of error.
+    #[must_use]
+    pub fn into_kind(self) -> DecodeError {
+        self.kind
+    }
+}
+
+impl From<DetailedDecodeError> for DecodeError {
+    fn from(value: DetailedDecodeError) -> Self {
+        value.kind
+    }
+}
+
+impl DetailedDecodeError {
+    pub(crate) fn new(kind: DecodeError, context: DecodeErrorContext) -> Self {
+        DetailedDecodeError { kind, context }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for DetailedDecodeError {
+    fn from(value: std::io::Error) -> Self {
+        DetailedDecodeError::new(value.into(), DecodeErrorContext::default())
+    }
+}
+
+impl DoipCodec {
+    /// Decodes a frame in the same way as `decode_from_bytes`, adding the
+    /// payload type, offset, raw value and lengths involved to any error.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DetailedDecodeError` if the message could not be decoded.
+    pub fn decode_detailed(&self, src: &[u8]) -> Result<Option<DoipFrame>, DetailedDecodeError> {
+        self.decode_frame(src)
+    }
+
+    /// Turns the codec into a [`DetailedCodec`], which keeps the context of
+    /// decode errors on streams.
+    #[must_use]
+    pub fn into_detailed(self) -> DetailedCodec {
+        DetailedCodec { codec: self }
+    }
+}
+
+/// A `DoipCodec` whose stream decoder returns [`DetailedDecodeError`]s.
+///
+/// Used with `tokio_util::codec::Framed` in place of the plain codec, it
+/// decodes and recovers in the same way but keeps the context of errors.
+#[derive(Debug, Default)]
+pub struct DetailedCodec {
+    codec: DoipCodec,
+}
+
+impl DetailedCodec {
+    /// Creates a detailed codec with the default `DoipCodec` configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        DetailedCodec::default()
+    }
+
+    /// Returns the underlying codec.
+    #[must_use]
+    pub fn codec(&self) -> &DoipCodec {
+        &self.codec
+    }
+
+    /// Returns the underlying codec mutably.
+    pub fn codec_mut(&mut self) -> &mut DoipCodec {
+        &mut self.codec
+    }
+
+    /// Returns the underlying codec.
+    #[must_use]
+    pub fn into_inner(self) -> DoipCodec {
+        self.codec
+    }
+}
+
+impl From<DoipCodec> for DetailedCodec {
+    fn from(value: DoipCodec) -> Self {
+        value.into_detailed()
+    }
+}
+
+#[cfg(feature = "std")]
+impl tokio_util::codec::Decoder for DetailedCodec {
+    type Item = DoipFrame;
+    type Error = DetailedDecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.codec.decode_stream(src)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Item> tokio_util::codec::Encoder<Item> for DetailedCodec
+where
+    DoipCodec: Encoder<Item, Error = EncodeError>,
+{
+    type Error = EncodeError;
+
+    fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        tokio_util::codec::Encoder::encode(&mut self.codec, item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    use crate::{DecodeError, DecodeErrorContext, DoipCodec, DoipFrame, RecoveryPolicy};
+
+    #[test]
+    fn test_decode_detailed_invalid_value() {
+        let bytes = [0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01, 0x7f];
+
+        let err = DoipCodec::new().decode_detailed(&bytes).unwrap_err();
+
+        assert!(matches!(err.kind(), DecodeError::InvalidPowerMode));
+        assert_eq!(
+            *err.context(),
+            DecodeErrorContext {
+                payload_type: Some(0x4004),
+                offset: Some(8),
+                value: Some(0x7f),
+                expected_length: None,
+                actual_length: None,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid power mode (payload type 0x4004, offset 8, value 0x7f)"
+        );
+    }
+
+    #[test]
+    fn test_decode_detailed_invalid_length() {
+        let bytes = [0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00];
+
+        let err = DoipCodec::new()
+            .with_strict_length(true)
+            .decode_detailed(&bytes)
+            .unwrap_err();
+
+        assert!(matches!(err.kind(), DecodeError::InvalidPayloadLength));
+        assert_eq!(err.context().offset, Some(4));
+        assert_eq!(err.context().expected_length, Some(0));
+        assert_eq!(err.context().actual_length, Some(1));
+    }
+
+    #[test]
+    fn test_decode_detailed_message_too_large() {
+        let bytes = [0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x0b];
+
+        let err = DoipCodec::new()
+            .with_max_payload_length(4)
+            .decode_detailed(&bytes)
+            .unwrap_err();
+
+        assert!(matches!(err.into_kind(), DecodeError::MessageTooLarge));
+    }
+
+    #[test]
+    fn test_decode_detailed_invalid_inverse_version() {
+        let bytes = [0x02, 0xff, 0x80, 0x01, 0x00, 0x00, 0x00, 0x0b];
+
+        let err = DoipCodec::new().decode_detailed(&bytes).unwrap_err();
+
+        assert!(matches!(err.kind(), DecodeError::FailedProtocolValidation));
+        assert_eq!(err.context().offset, Some(1));
+        assert_eq!(err.context().value, Some(0xff));
+    }
+
+    #[test]
+    fn test_decode_detailed_payload_too_short() {
+        let bytes = [0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x02, 0x0e, 0x00];
+
+        let err = DoipCodec::new().decode_detailed(&bytes).unwrap_err();
+
+        assert!(matches!(err.kind(), DecodeError::TooShort));
+        assert_eq!(err.context().offset, Some(4));
+        assert_eq!(err.context().expected_length, Some(5));
+        assert_eq!(err.context().actual_length, Some(2));
+    }
+
+    #[test]
+    fn test_decode_detailed_invalid_payload_type() {
+        let bytes = [0x02, 0xfd, 0xf0, 0x01, 0x00, 0x00, 0x00, 0x00];
+
+        let err = DoipCodec::new().decode_detailed(&bytes).unwrap_err();
+
+        assert!(matches!(err.kind(), DecodeError::InvalidPayloadType));
+        assert_eq!(err.context().offset, Some(2));
+        assert_eq!(err.context().value, Some(0xf001));
+    }
+
+    #[test]
+    fn test_context_with_field() {
+        let src = [0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x05, 0x0e, 0x80];
+
+        let context = DecodeErrorContext::default().with_field(&src, 8, 2);
+        assert_eq!(context.offset, Some(8));
+        assert_eq!(context.value, Some(0x0e80));
+
+        let context = DecodeErrorContext::default().with_field(&src, 9, 2);
+        assert_eq!(context.value, None);
+    }
+
+    #[test]
+    fn test_detailed_codec_stream() {
+        let bytes = [
+            0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01, 0x7f, 0x02, 0xfd, 0x00, 0x07, 0x00,
+            0x00, 0x00, 0x00,
+        ];
+        let mut codec = DoipCodec::new()
+            .with_recovery_policy(RecoveryPolicy::SkipFrame)
+            .into_detailed();
+        let mut src = BytesMut::from(&bytes[..]);
+
+        let err = codec.decode(&mut src).unwrap_err();
+        assert!(matches!(err.kind(), DecodeError::InvalidPowerMode));
+        assert_eq!(err.context().offset, Some(8));
+        assert_eq!(err.context().value, Some(0x7f));
+
+        let frame = codec.decode(&mut src).unwrap();
+        assert!(matches!(frame, Some(DoipFrame::Message(_))));
+        assert!(src.is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+use alloc::{boxed::Box, collections::BTreeMap};
+use core::{any::Any, fmt};
+
+use doip_definitions::header::ProtocolVersion;
+
+use bytes::BufMut;
+
+use crate::{DecodeError, EncodeError};
+
+/// Codec for an application defined payload type, such as the OEM-specific
+/// payload types in the range `0xF000..=0xFFFF`.
+///
+/// Implementations are registered on a `DoipCodec` for a payload type code via
+/// `DoipCodec::with_custom_payload`. Codes of the payload types defined by
+/// ISO 13400-2 are always handled by the codec itself.
+pub trait CustomPayloadCodec: Send + Sync + 'static {
+    /// The type of the decoded payload.
+    type Payload: fmt::Debug + Clone + PartialEq + Send + Sync + 'static;
+
+    /// Decodes the payload of a message, `src` contains exactly the number of
+    /// bytes announced in the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DecodeError` if the bytes do not form a valid payload.
+    fn decode(&self, src: &[u8]) -> Result<Self::Payload, DecodeError>;
+
+    /// Returns the exact number of bytes `encode` writes for the payload, the
+    /// value is used as the payload length of the header.
+    fn encoded_len(&self, item: &Self::Payload) -> usize;
+
+    /// Encodes the payload of a message into the buffer provided.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `EncodeError` if the payload could not be encoded.
+    fn encode(&self, item: &Self::Payload, dst: &mut dyn BufMut) -> Result<(), EncodeError>;
+}
+
+/// A message whose payload has been decoded by a registered
+/// [`CustomPayloadCodec`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomMessage {
+    /// Protocol version of the message.
+    pub protocol_version: ProtocolVersion,
+
+    /// Payload type code the payload codec is registered for.
+    pub payload_type: u16,
+
+    /// The decoded payload.
+    pub payload: CustomPayload,
+}
+
+/// Type erased payload produced by a [`CustomPayloadCodec`].
+pub struct CustomPayload(Box<dyn ErasedPayload>);
+
+impl CustomPayload {
+    /// Wraps an application defined payload.
+    pub fn new<T>(value: T) -> Self
+    where
+        T: fmt::Debug + Clone + PartialEq + Send + Sync + 'static,
+    {
+        CustomPayload(Box::new(value))
+    }
+
+    /// Returns a reference to the payload if it is of type `T`.
+    #[must_use]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+
+    /// Returns `true` if the payload is of type `T`.
+    #[must_use]
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.as_any().is::<T>()
+    }
+}
+
+impl fmt::Debug for CustomPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Clone for CustomPayload {
+    fn clone(&self) -> Self {
+        CustomPayload(self.0.clone_box())
+    }
+}
+
+impl PartialEq for CustomPayload {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_dyn(other.0.as_any())
+    }
+}
+
+trait ErasedPayload: fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn clone_box(&self) -> Box<dyn ErasedPayload>;
+    fn eq_dyn(&self, other: &dyn Any) -> bool;
+}
+
+impl<T> ErasedPayload for T
+where
+    T: fmt::Debug + Clone + PartialEq + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn ErasedPayload> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+}
+
+trait ErasedCodec: Send + Sync {
+    fn decode(&self, src: &[u8]) -> Result<CustomPayload, DecodeError>;
+    fn encoded_len(&self, item: &CustomPayload) -> Option<usize>;
+    fn encode(&self, item: &CustomPayload, dst: &mut dyn BufMut) -> Result<(), EncodeError>;
+}
+
+impl<C: CustomPayloadCodec> ErasedCodec for C {
+    fn decode(&self, src: &[u8]) -> Result<CustomPayload, DecodeError> {
+        CustomPayloadCodec::decode(self, src).map(CustomPayload::new)
+    }
+
+    fn encoded_len(&self, item: &CustomPayload) -> Option<usize> {
+        item.downcast_ref::<C::Payload>()
+            .map(|item| CustomPayloadCodec::encoded_len(self, item))
+    }
+
+    fn encode(&self, item: &CustomPayload, dst: &mut dyn BufMut) -> Result<(), EncodeError> {
+        let item = item
+            .downcast_ref::<C::Payload>()
+            .ok_or(EncodeError::PayloadTypeValidation)?;
+
+        CustomPayloadCodec::encode(self, item, dst)
+    }
+}
+
+/// Payload codecs registered on a `DoipCodec`, keyed by payload type code.
+#[derive(Default)]
+pub(crate) struct PayloadRegistry {
+    codecs: BTreeMap<u16, Box<dyn ErasedCodec>>,
+}
+
+impl PayloadRegistry {
+    pub(crate) fn register<C: CustomPayloadCodec>(&mut self, payload_type: u16, codec: C) {
+        self.codecs.insert(payload_type, Box::new(codec));
+    }
+
+    pub(crate) fn contains(&self, payload_type: u16) -> bool {
+        self.codecs.contains_key(&payload_type)
+    }
+
+    pub(crate) fn decode(
+        &self,
+        payload_type: u16,
+        src: &[u8],
+    ) -> Option<Result<CustomPayload, DecodeError>> {
+        self.codecs
+            .get(&payload_type)
+            .map(|codec| codec.decode(src))
+    }
+
+    pub(crate) fn encoded_len(&self, payload_type: u16, item: &CustomPayload) -> Option<usize> {
+        self.codecs
+            .get(&payload_type)
+            .and_then(|codec| codec.encoded_len(item))
+    }
+
+    pub(crate) fn encode(
+        &self,
+        payload_type: u16,
+        item: &CustomPayload,
+        dst: &mut dyn BufMut,
+    ) -> Result<(), EncodeError> {
+        self.codecs
+            .get(&payload_type)
+            .ok_or(EncodeError::PayloadTypeValidation)?
+            .encode(item, dst)
+    }
+}
+
+impl fmt::Debug for PayloadRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayloadRegistry")
+            .field("payload_types", &self.codecs.keys())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+    use doip_definitions::header::ProtocolVersion;
+
+    use crate::{
+        CustomMessage, CustomPayload, CustomPayloadCodec, DecodeError, Decoder, DoipCodec,
+        DoipFrame, EncodeError, Encoder,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct VendorPing {
+        counter: u16,
+    }
+
+    struct VendorPingCodec;
+
+    impl CustomPayloadCodec for VendorPingCodec {
+        type Payload = VendorPing;
+
+        fn decode(&self, src: &[u8]) -> Result<Self::Payload, DecodeError> {
+            let counter = src.try_into().map_err(|_| DecodeError::TooShort)?;
+
+            Ok(VendorPing {
+                counter: u16::from_be_bytes(counter),
+            })
+        }
+
+        fn encoded_len(&self, _item: &Self::Payload) -> usize {
+            2
+        }
+
+        fn encode(&self, item: &Self::Payload, dst: &mut dyn BufMut) -> Result<(), EncodeError> {
+            dst.put_u16(item.counter);
+
+            Ok(())
+        }
+    }
+
+    fn vendor_ping() -> CustomMessage {
+        CustomMessage {
+            protocol_version: ProtocolVersion::Iso13400_2012,
+            payload_type: 0xf001,
+            payload: CustomPayload::new(VendorPing { counter: 0x0102 }),
+        }
+    }
+
+    #[test]
+    fn test_encode_custom_payload_success() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, VendorPingCodec);
+        let mut dst = Vec::<u8>::new();
+
+        let res = codec.to_bytes(vendor_ping(), &mut dst);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            *dst,
+            [0x02, 0xfd, 0xf0, 0x01, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02]
+        );
+    }
+
+    #[test]
+    fn test_decode_custom_payload_success() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, VendorPingCodec);
+        let mut dst = Vec::<u8>::new();
+
+        let _ = codec.to_bytes(vendor_ping(), &mut dst);
+        let msg = codec.decode_from_bytes(&dst);
+
+        assert!(msg.is_ok());
+        let res = msg.unwrap().unwrap();
+
+        assert_eq!(res, DoipFrame::Custom(vendor_ping()));
+
+        let DoipFrame::Custom(custom) = res else {
+            unreachable!()
+        };
+        assert!(custom.payload.is::<VendorPing>());
+        assert_eq!(
+            custom.payload.downcast_ref::<VendorPing>(),
+            Some(&VendorPing { counter: 0x0102 })
+        );
+    }
+
+    #[test]
+    fn test_decode_custom_payload_unregistered() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, VendorPingCodec);
+
+        let bytes = &[0x02, 0xfd, 0xf0, 0x02, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02];
+        let msg = codec.decode_from_bytes(bytes);
+
+        assert!(matches!(msg, Err(DecodeError::InvalidPayloadType)));
+    }
+
+    #[test]
+    fn test_encode_custom_payload_unregistered() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        let res = codec.to_bytes(vendor_ping(), &mut dst);
+
+        assert!(matches!(res, Err(EncodeError::PayloadTypeValidation)));
+    }
+
+    #[test]
+    fn test_encode_custom_payload_type_mismatch() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, VendorPingCodec);
+        let mut dst = Vec::<u8>::new();
+
+        let mut item = vendor_ping();
+        item.payload = CustomPayload::new(42u8);
+        let res = codec.to_bytes(item, &mut dst);
+
+        assert!(matches!(res, Err(EncodeError::PayloadTypeValidation)));
+    }
+
+    /// Codec whose `encoded_len` does not match what `encode` writes.
+    struct OverlongCodec;
+
+    impl CustomPayloadCodec for OverlongCodec {
+        type Payload = VendorPing;
+
+        fn decode(&self, _src: &[u8]) -> Result<Self::Payload, DecodeError> {
+            Err(DecodeError::InvalidPayload)
+        }
+
+        fn encoded_len(&self, _item: &Self::Payload) -> usize {
+            1
+        }
+
+        fn encode(&self, item: &Self::Payload, dst: &mut dyn BufMut) -> Result<(), EncodeError> {
+            dst.put_u16(item.counter);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_custom_payload_length_mismatch() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, OverlongCodec);
+
+        let mut dst = BytesMut::new();
+        let res = tokio_util::codec::Encoder::encode(&mut codec, vendor_ping(), &mut dst);
+        assert!(matches!(res, Err(EncodeError::PayloadLengthValidation)));
+        assert!(dst.is_empty());
+
+        let mut buf = [0u8; 16];
+        let res = codec.encode_to_slice(vendor_ping(), &mut buf);
+        assert!(matches!(res, Err(EncodeError::PayloadLengthValidation)));
+    }
+
+    #[test]
+    fn test_encode_custom_payload_buffer_too_small() {
+        let mut codec = DoipCodec::new().with_custom_payload(0xf001, VendorPingCodec);
+        let mut buf = [0u8; 9];
+
+        let res = codec.to_bytes(vendor_ping(), &mut &mut buf[..]);
+
+        assert!(matches!(res, Err(EncodeError::BufferTooSmall)));
+        assert_eq!(buf, [0u8; 9]);
+    }
+}
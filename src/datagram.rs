@@ -0,0 +1,199 @@
+use crate::{decoder::frame_length, DecodeError, Decoder, DoipCodec, DoipFrame};
+
+#[cfg(feature = "std")]
+use crate::{EncodeError, Encoder};
+#[cfg(feature = "std")]
+use bytes::BytesMut;
+
+impl DoipCodec {
+    /// Decodes a datagram which has to hold exactly one message, as used for
+    /// vehicle identification and announcement over UDP.
+    ///
+    /// No state is kept between datagrams, in particular a truncated message is
+    /// never completed by the next datagram.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecodeError::TooShort` if the datagram ends before the message,
+    /// `DecodeError::ExceededLength` if it holds bytes after the message, and
+    /// any other `DecodeError` if the message is malformed.
+    pub fn decode_datagram(&mut self, datagram: &[u8]) -> Result<DoipFrame, DecodeError> {
+        let frame = self
+            .decode_from_bytes(datagram)?
+            .ok_or(DecodeError::TooShort)?;
+
+        if datagram.len() > frame_length(datagram)? {
+            return Err(DecodeError::ExceededLength);
+        }
+
+        Ok(frame)
+    }
+
+    /// Turns the codec into a [`DatagramCodec`] for use with UDP sockets.
+    #[must_use]
+    pub fn into_datagram(self) -> DatagramCodec {
+        DatagramCodec { codec: self }
+    }
+}
+
+/// A `DoipCodec` in datagram mode, where every datagram holds exactly one
+/// message.
+///
+/// Used with `tokio_util::udp::UdpFramed`, each received datagram yields one
+/// item together with the address of its sender. Decode errors are part of the
+/// item rather than errors of the stream, so that a single malformed datagram
+/// neither ends the stream nor loses track of its sender. The read buffer is
+/// always emptied after a datagram has been decoded.
+#[derive(Debug, Default)]
+pub struct DatagramCodec {
+    codec: DoipCodec,
+}
+
+impl DatagramCodec {
+    /// Creates a datagram codec with the default `DoipCodec` configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        DatagramCodec::default()
+    }
+
+    /// Returns the underlying codec.
+    #[must_use]
+    pub fn codec(&self) -> &DoipCodec {
+        &self.codec
+    }
+
+    /// Returns the underlying codec mutably.
+    pub fn codec_mut(&mut self) -> &mut DoipCodec {
+        &mut self.codec
+    }
+
+    /// Returns the underlying codec, leaving datagram mode.
+    #[must_use]
+    pub fn into_inner(self) -> DoipCodec {
+        self.codec
+    }
+}
+
+impl From<DoipCodec> for DatagramCodec {
+    fn from(value: DoipCodec) -> Self {
+        value.into_datagram()
+    }
+}
+
+#[cfg(feature = "std")]
+impl tokio_util::codec::Decoder for DatagramCodec {
+    type Item = Result<DoipFrame, DecodeError>;
+    type Error = DecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        let decoded = self.codec.decode_datagram(src);
+        src.clear();
+
+        Ok(Some(decoded))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Item> tokio_util::codec::Encoder<Item> for DatagramCodec
+where
+    DoipCodec: Encoder<Item, Error = EncodeError>,
+{
+    type Error = EncodeError;
+
+    fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        tokio_util::codec::Encoder::encode(&mut self.codec, item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::UdpSocket;
+    use tokio_util::udp::UdpFramed;
+
+    use crate::{DecodeError, DoipCodec, DoipFrame};
+
+    static VEHICLE_IDENTIFICATION_REQUEST: [u8; 8] =
+        [0x02, 0xfd, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_decode_datagram() {
+        let mut codec = DoipCodec::new();
+
+        let res = codec.decode_datagram(&VEHICLE_IDENTIFICATION_REQUEST);
+        assert!(matches!(res, Ok(DoipFrame::Message(_))));
+
+        let res = codec.decode_datagram(&VEHICLE_IDENTIFICATION_REQUEST[..6]);
+        assert!(matches!(res, Err(DecodeError::TooShort)));
+
+        let mut trailing = VEHICLE_IDENTIFICATION_REQUEST.to_vec();
+        trailing.push(0x00);
+        let res = codec.decode_datagram(&trailing);
+        assert!(matches!(res, Err(DecodeError::ExceededLength)));
+    }
+
+    #[test]
+    fn test_decode_datagram_keeps_no_state() {
+        let mut codec = DoipCodec::new();
+        let diag = [
+            0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x05, 0x0e, 0x00, 0x10, 0x01, 0x3e,
+        ];
+
+        let res = codec.decode_datagram(&diag[..10]);
+        assert!(matches!(res, Err(DecodeError::TooShort)));
+
+        let res = codec.decode_datagram(&diag[10..]);
+        assert!(res.is_err());
+
+        let res = codec.decode_datagram(&VEHICLE_IDENTIFICATION_REQUEST);
+        assert!(matches!(res, Ok(DoipFrame::Message(_))));
+    }
+
+    #[tokio::test]
+    async fn test_udp_framed() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender_addr = sender.local_addr().unwrap();
+        let receiver_addr = receiver.local_addr().unwrap();
+
+        let mut framed = UdpFramed::new(receiver, DoipCodec::new().into_datagram());
+
+        let mut trailing = VEHICLE_IDENTIFICATION_REQUEST.to_vec();
+        trailing.push(0x00);
+
+        sender.send_to(&trailing, receiver_addr).await.unwrap();
+        sender
+            .send_to(&VEHICLE_IDENTIFICATION_REQUEST[..6], receiver_addr)
+            .await
+            .unwrap();
+        sender
+            .send_to(&VEHICLE_IDENTIFICATION_REQUEST, receiver_addr)
+            .await
+            .unwrap();
+
+        let (item, addr) = framed.next().await.unwrap().unwrap();
+        assert!(matches!(item, Err(DecodeError::ExceededLength)));
+        assert_eq!(addr, sender_addr);
+
+        let (item, addr) = framed.next().await.unwrap().unwrap();
+        assert!(matches!(item, Err(DecodeError::TooShort)));
+        assert_eq!(addr, sender_addr);
+
+        let (item, addr) = framed.next().await.unwrap().unwrap();
+        let Ok(frame) = item else {
+            panic!("Expected a vehicle identification request");
+        };
+        assert_eq!(addr, sender_addr);
+
+        framed.send((frame, sender_addr)).await.unwrap();
+
+        let mut buf = [0u8; 16];
+        let (len, addr) = sender.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], VEHICLE_IDENTIFICATION_REQUEST);
+        assert_eq!(addr, receiver_addr);
+    }
+}
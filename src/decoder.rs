@@ -1,9 +1,25 @@
-use doip_definitions::{definitions::DOIP_HEADER_LEN, header::PayloadType, message::DoipMessage};
-use tokio_util::bytes::Buf;
+use bytes::{Buf, BytesMut};
+use doip_definitions::{
+    definitions::{
+        DOIP_ALIVE_CHECK_RESPONSE_SOURCE_LEN, DOIP_COMMON_EID_LEN, DOIP_COMMON_VIN_LEN,
+        DOIP_DIAG_COMMON_SOURCE_LEN, DOIP_DIAG_COMMON_TARGET_LEN, DOIP_DIAG_MESSAGE_ACK_CODE_LEN,
+        DOIP_DIAG_MESSAGE_ACK_CODE_OFFSET, DOIP_DIAG_MESSAGE_NACK_CODE_LEN,
+        DOIP_DIAG_MESSAGE_NACK_CODE_OFFSET, DOIP_ENTITY_STATUS_RESPONSE_LEN, DOIP_GENERIC_NACK_LEN,
+        DOIP_HEADER_LEN, DOIP_INV_VERSION_OFFSET, DOIP_LENGTH_LEN, DOIP_LENGTH_OFFSET,
+        DOIP_POWER_MODE_LEN, DOIP_ROUTING_ACTIVATION_REQ_LEN,
+        DOIP_ROUTING_ACTIVATION_REQ_TYPE_OFFSET, DOIP_ROUTING_ACTIVATION_RES_CODE_OFFSET,
+        DOIP_ROUTING_ACTIVATION_RES_LEN, DOIP_TYPE_LEN, DOIP_TYPE_OFFSET,
+        DOIP_VEHICLE_ANNOUNCEMENT_ACTION_OFFSET, DOIP_VEHICLE_ANNOUNCEMENT_LEN_LONG,
+        DOIP_VEHICLE_ANNOUNCEMENT_LEN_SHORT, DOIP_VEHICLE_ANNOUNCEMENT_SYNC_OFFSET,
+        DOIP_VERSION_OFFSET,
+    },
+    header::{PayloadType, ProtocolVersion},
+    message::DoipMessage,
+};
 
 use crate::{
     doip_message::{
-        header::HeaderCodec,
+        header::{validate_protocol, HeaderCodec},
         payload::{
             alive_check_request::AliveCheckRequestCodec as AlivChecReqCodec,
             alive_check_response::AliveCheckResponseCodec as AlivChecResCodec,
@@ -24,91 +40,517 @@ use crate::{
         },
     },
     error::DecodeError,
-    Decoder, DoipCodec,
+    CustomMessage, DecodeErrorContext, Decoder, DetailedDecodeError, DoipCodec, DoipFrame,
+    ExtendedMessage, FromBytes, PayloadExtension, RawMessage, RecoveryPolicy,
+    DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT, DOIP_ROUTING_ACTIVATION_OEM_LEN,
 };
 
+/// Length of the protocol version fields of the header and of the payload fields
+/// holding a code, such as NACK and activation codes.
+const BYTE_FIELD_LEN: usize = 1;
+
 impl Decoder for DoipCodec {
-    type Item = DoipMessage;
+    type Item = DoipFrame;
     type Error = DecodeError;
 
     fn decode_from_bytes(&mut self, src: &[u8]) -> Result<Option<Self::Item>, Self::Error> {
+        let decoded = self
+            .decode_frame(src)
+            .map_err(DetailedDecodeError::into_kind)?;
+        self.check_decoded(decoded.as_ref());
+
+        Ok(decoded)
+    }
+}
+
+impl DoipCodec {
+    /// Decodes the frame at the start of `src`, recording the context of an
+    /// error where it is detected.
+    pub(crate) fn decode_frame(
+        &self,
+        src: &[u8],
+    ) -> Result<Option<DoipFrame>, DetailedDecodeError> {
         if src.len() < DOIP_HEADER_LEN {
             return Ok(None);
         }
 
+        self.validate_header(src)?;
+
+        let context = DecodeErrorContext::of_header(src);
         let mut h_codec = HeaderCodec {};
 
-        let header = h_codec
-            .decode_from_bytes(src)?
-            .expect("Should never return Ok(None)");
+        let header = match h_codec.decode_from_bytes(src) {
+            Ok(header) => header.expect("Should never return Ok(None)"),
+            Err(DecodeError::InvalidPayloadType) => return self.decode_unknown(src),
+            Err(err) => return Err(DetailedDecodeError::new(err, context)),
+        };
+
+        let frame_length = frame_length(src).map_err(|err| {
+            DetailedDecodeError::new(err, context.with_length(None, header.payload_length))
+        })?;
+
+        // Payload codecs only ever see a single, complete frame
+        let Some(src) = src.get(..frame_length) else {
+            return Ok(None);
+        };
 
         let payload = match header.payload_type {
-            PayloadType::GenericNack => GenericNackCodec {}.decode_from_bytes(src)?,
-            PayloadType::VehicleIdentificationRequest => VehIDReqCodec {}.decode_from_bytes(src)?,
+            PayloadType::GenericNack => GenericNackCodec {}.decode_from_bytes(src),
+            PayloadType::VehicleIdentificationRequest => VehIDReqCodec {}.decode_from_bytes(src),
             PayloadType::VehicleIdentificationRequestEid => {
-                VehIDReqEidCodec {}.decode_from_bytes(src)?
+                VehIDReqEidCodec {}.decode_from_bytes(src)
             }
             PayloadType::VehicleIdentificationRequestVin => {
-                VehIDReqVinCodec {}.decode_from_bytes(src)?
+                VehIDReqVinCodec {}.decode_from_bytes(src)
+            }
+            PayloadType::VehicleAnnouncementMessage => VehAnnMsgCodec {}.decode_from_bytes(src),
+            PayloadType::RoutingActivationRequest => RoutActReqCodec {}.decode_from_bytes(src),
+            PayloadType::RoutingActivationResponse => RoutActResCodec {}.decode_from_bytes(src),
+            PayloadType::AliveCheckRequest => AlivChecReqCodec {}.decode_from_bytes(src),
+            PayloadType::AliveCheckResponse => AlivChecResCodec {}.decode_from_bytes(src),
+            PayloadType::EntityStatusRequest => EntStatReqCodec {}.decode_from_bytes(src),
+            PayloadType::EntityStatusResponse => EntStatResCodec {}.decode_from_bytes(src),
+            PayloadType::PowerInformationRequest => PowInfoReqCodec {}.decode_from_bytes(src),
+            PayloadType::PowerInformationResponse => PowInfoResCodec {}.decode_from_bytes(src),
+            PayloadType::DiagnosticMessage => DiagMsgCodec {}.decode_from_bytes(src),
+            PayloadType::DiagnosticMessageAck => DiagMsgAckCodec {}.decode_from_bytes(src),
+            PayloadType::DiagnosticMessageNack => DiagMsgNackCodec {}.decode_from_bytes(src),
+        };
+
+        let payload = match payload {
+            Err(err) if self.lenient && err.is_unknown_value() => {
+                return Ok(Some(DoipFrame::Raw(RawMessage {
+                    protocol_version: header.protocol_version,
+                    payload_type: header.payload_type as u16,
+                    payload: src[DOIP_HEADER_LEN..].to_vec(),
+                })));
+            }
+            Err(err) => {
+                let context = match value_offset(header.payload_type, &err) {
+                    Some(offset) => context.with_field(src, offset, BYTE_FIELD_LEN),
+                    None => context.with_length(
+                        Some(AllowedLength::of(header.payload_type).min()),
+                        header.payload_length,
+                    ),
+                };
+
+                return Err(DetailedDecodeError::new(err, context));
+            }
+            Ok(payload) => payload
+                .expect("Should never fail, this means header has been mutated during runtime"),
+        };
+
+        let message = DoipMessage { header, payload };
+
+        let extension = PayloadExtension::decode(&message.header, src)
+            .map_err(|err| DetailedDecodeError::new(err, context))?;
+
+        match extension {
+            Some(extension) => Ok(Some(DoipFrame::Extended(ExtendedMessage {
+                message,
+                extension,
+            }))),
+            None => Ok(Some(DoipFrame::Message(message))),
+        }
+    }
+
+    /// Checks the header at the start of `src` against the configuration of
+    /// the codec: the protocol version and its inverse, the supported versions,
+    /// the payload type, the payload length limits and, in strict mode, the
+    /// payload length allowed for the payload type.
+    ///
+    /// Shared by all decoders, so that they accept the same messages. `src`
+    /// must contain at least a full header.
+    pub(crate) fn validate_header(&self, src: &[u8]) -> Result<(), DetailedDecodeError> {
+        let context = DecodeErrorContext::of_header(src);
+        let field_error = |err, offset| {
+            DetailedDecodeError::new(err, context.with_field(src, offset, BYTE_FIELD_LEN))
+        };
+
+        let protocol_version = ProtocolVersion::from_bytes(&src[DOIP_VERSION_OFFSET..])
+            .ok_or_else(|| field_error(DecodeError::InvalidProtocolVersion, DOIP_VERSION_OFFSET))?;
+
+        validate_protocol(src[DOIP_VERSION_OFFSET], src[DOIP_INV_VERSION_OFFSET]).ok_or_else(
+            || {
+                field_error(
+                    DecodeError::FailedProtocolValidation,
+                    DOIP_INV_VERSION_OFFSET,
+                )
+            },
+        )?;
+
+        let payload_type_code = u16::from_be_bytes(
+            src[DOIP_TYPE_OFFSET..DOIP_TYPE_OFFSET + DOIP_TYPE_LEN]
+                .try_into()
+                .expect("Slice is always the correct length"),
+        );
+        let payload_type = PayloadType::from_bytes(&src[DOIP_TYPE_OFFSET..]);
+
+        if payload_type.is_none()
+            && !self.raw_pass_through
+            && !self.custom_payloads.contains(payload_type_code)
+        {
+            return Err(DetailedDecodeError::new(
+                DecodeError::InvalidPayloadType,
+                context.with_field(src, DOIP_TYPE_OFFSET, DOIP_TYPE_LEN),
+            ));
+        }
+
+        self.validate_protocol_version(protocol_version, payload_type_code)
+            .map_err(|err| field_error(err, DOIP_VERSION_OFFSET))?;
+
+        let payload_length = u32::from_be_bytes(
+            src[DOIP_LENGTH_OFFSET..DOIP_LENGTH_OFFSET + DOIP_LENGTH_LEN]
+                .try_into()
+                .expect("Slice is always the correct length"),
+        );
+        let length_error = |err, expected| {
+            DetailedDecodeError::new(err, context.with_length(Some(expected), payload_length))
+        };
+
+        if payload_length > self.max_payload_length {
+            return Err(length_error(
+                DecodeError::MessageTooLarge,
+                self.max_payload_length as usize,
+            ));
+        }
+
+        if payload_length > self.available_memory {
+            return Err(length_error(
+                DecodeError::OutOfMemory,
+                self.available_memory as usize,
+            ));
+        }
+
+        match payload_type {
+            Some(payload_type)
+                if self.strict_length && !is_allowed_length(payload_type, payload_length) =>
+            {
+                Err(length_error(
+                    DecodeError::InvalidPayloadLength,
+                    AllowedLength::of(payload_type).min(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn validate_protocol_version(
+        &self,
+        protocol_version: ProtocolVersion,
+        payload_type: u16,
+    ) -> Result<(), DecodeError> {
+        let supported = if protocol_version == ProtocolVersion::DefaultValue {
+            payload_type == PayloadType::VehicleIdentificationRequest as u16
+                || payload_type == PayloadType::VehicleIdentificationRequestEid as u16
+                || payload_type == PayloadType::VehicleIdentificationRequestVin as u16
+        } else {
+            self.supported_versions
+                .as_ref()
+                .is_none_or(|versions| versions.contains(&protocol_version))
+        };
+
+        if supported {
+            Ok(())
+        } else {
+            Err(DecodeError::UnsupportedProtocolVersion)
+        }
+    }
+
+    /// Decodes a message whose payload type is not defined by ISO 13400-2,
+    /// either through a registered custom payload codec or as raw pass-through.
+    /// The header has already been checked by `validate_header`.
+    fn decode_unknown(&self, src: &[u8]) -> Result<Option<DoipFrame>, DetailedDecodeError> {
+        let context = DecodeErrorContext::of_header(src);
+        let payload_type = u16::from_be_bytes(
+            src[DOIP_TYPE_OFFSET..DOIP_TYPE_OFFSET + DOIP_TYPE_LEN]
+                .try_into()
+                .expect("Slice is always the correct length"),
+        );
+
+        let protocol_version = ProtocolVersion::from_bytes(&src[DOIP_VERSION_OFFSET..])
+            .expect("Protocol version has been validated");
+
+        let payload_length = frame_length(src)
+            .map_err(|err| DetailedDecodeError::new(err, context))?
+            - DOIP_HEADER_LEN;
+
+        let Some(payload) = src.get(DOIP_HEADER_LEN..DOIP_HEADER_LEN + payload_length) else {
+            return Ok(None);
+        };
+
+        let frame = match self.custom_payloads.decode(payload_type, payload) {
+            Some(custom) => DoipFrame::Custom(CustomMessage {
+                protocol_version,
+                payload_type,
+                payload: custom.map_err(|err| DetailedDecodeError::new(err, context))?,
+            }),
+            None => DoipFrame::Raw(RawMessage {
+                protocol_version,
+                payload_type,
+                payload: payload.to_vec(),
+            }),
+        };
+
+        Ok(Some(frame))
+    }
+
+    pub(crate) fn discard(&mut self, src: &mut BytesMut) {
+        let discard_length = self.discard_remaining.min(src.len());
+        src.advance(discard_length);
+        self.discard_remaining -= discard_length;
+    }
+
+    /// Drops the frame at the start of `src` based on the length announced in
+    /// its header, including any parts of it which have not been received yet.
+    ///
+    /// A frame too large to be addressed is never received completely, so
+    /// everything following its header is dropped.
+    pub(crate) fn skip_frame(&mut self, src: &mut BytesMut) {
+        self.discard_remaining = frame_length(src).unwrap_or(usize::MAX);
+        self.discard(src);
+    }
+
+    pub(crate) fn recover(&mut self, src: &mut BytesMut, err: &DecodeError) {
+        let header_invalid = matches!(
+            err,
+            DecodeError::InvalidProtocolVersion | DecodeError::FailedProtocolValidation
+        );
+
+        match self.recovery_policy {
+            RecoveryPolicy::Fatal => {}
+            RecoveryPolicy::SkipFrame if !header_invalid => self.skip_frame(src),
+            RecoveryPolicy::SkipFrame | RecoveryPolicy::Resync => {
+                let offset = find_header_start(src);
+                src.advance(offset);
             }
-            PayloadType::VehicleAnnouncementMessage => VehAnnMsgCodec {}.decode_from_bytes(src)?,
-            PayloadType::RoutingActivationRequest => RoutActReqCodec {}.decode_from_bytes(src)?,
-            PayloadType::RoutingActivationResponse => RoutActResCodec {}.decode_from_bytes(src)?,
-            PayloadType::AliveCheckRequest => AlivChecReqCodec {}.decode_from_bytes(src)?,
-            PayloadType::AliveCheckResponse => AlivChecResCodec {}.decode_from_bytes(src)?,
-            PayloadType::EntityStatusRequest => EntStatReqCodec {}.decode_from_bytes(src)?,
-            PayloadType::EntityStatusResponse => EntStatResCodec {}.decode_from_bytes(src)?,
-            PayloadType::PowerInformationRequest => PowInfoReqCodec {}.decode_from_bytes(src)?,
-            PayloadType::PowerInformationResponse => PowInfoResCodec {}.decode_from_bytes(src)?,
-            PayloadType::DiagnosticMessage => DiagMsgCodec {}.decode_from_bytes(src)?,
-            PayloadType::DiagnosticMessageAck => DiagMsgAckCodec {}.decode_from_bytes(src)?,
-            PayloadType::DiagnosticMessageNack => DiagMsgNackCodec {}.decode_from_bytes(src)?,
-        }
-        .expect("Should never fail, this means header has been mutated during runtime");
-
-        Ok(Some(DoipMessage { header, payload }))
+        }
     }
 }
 
-impl tokio_util::codec::Decoder for DoipCodec {
-    type Item = DoipMessage;
-    type Error = DecodeError;
+/// Payload lengths ISO 13400-2 allows for a payload type.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum AllowedLength {
+    /// Exactly the given length.
+    Exact(usize),
+
+    /// The length without or with an optional trailing field.
+    Optional(usize, usize),
+
+    /// At least the given length.
+    AtLeast(usize),
+}
+
+impl AllowedLength {
+    pub(crate) fn of(payload_type: PayloadType) -> Self {
+        const DIAG_ADDRESSES_LEN: usize = DOIP_DIAG_COMMON_SOURCE_LEN + DOIP_DIAG_COMMON_TARGET_LEN;
+
+        match payload_type {
+            PayloadType::GenericNack => AllowedLength::Exact(DOIP_GENERIC_NACK_LEN),
+            PayloadType::VehicleIdentificationRequest
+            | PayloadType::AliveCheckRequest
+            | PayloadType::EntityStatusRequest
+            | PayloadType::PowerInformationRequest => AllowedLength::Exact(0),
+            PayloadType::VehicleIdentificationRequestEid => {
+                AllowedLength::Exact(DOIP_COMMON_EID_LEN)
+            }
+            PayloadType::VehicleIdentificationRequestVin => {
+                AllowedLength::Exact(DOIP_COMMON_VIN_LEN)
+            }
+            PayloadType::VehicleAnnouncementMessage => AllowedLength::Optional(
+                DOIP_VEHICLE_ANNOUNCEMENT_LEN_SHORT,
+                DOIP_VEHICLE_ANNOUNCEMENT_LEN_LONG,
+            ),
+            PayloadType::RoutingActivationRequest => AllowedLength::Optional(
+                DOIP_ROUTING_ACTIVATION_REQ_LEN,
+                DOIP_ROUTING_ACTIVATION_REQ_LEN + DOIP_ROUTING_ACTIVATION_OEM_LEN,
+            ),
+            PayloadType::RoutingActivationResponse => AllowedLength::Optional(
+                DOIP_ROUTING_ACTIVATION_RES_LEN,
+                DOIP_ROUTING_ACTIVATION_RES_LEN + DOIP_ROUTING_ACTIVATION_OEM_LEN,
+            ),
+            PayloadType::AliveCheckResponse => {
+                AllowedLength::Exact(DOIP_ALIVE_CHECK_RESPONSE_SOURCE_LEN)
+            }
+            PayloadType::EntityStatusResponse => AllowedLength::Optional(
+                DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT,
+                DOIP_ENTITY_STATUS_RESPONSE_LEN,
+            ),
+            PayloadType::PowerInformationResponse => AllowedLength::Exact(DOIP_POWER_MODE_LEN),
+            // At least one byte of user data has to follow the addresses
+            PayloadType::DiagnosticMessage => AllowedLength::AtLeast(DIAG_ADDRESSES_LEN + 1),
+            PayloadType::DiagnosticMessageAck => {
+                AllowedLength::AtLeast(DIAG_ADDRESSES_LEN + DOIP_DIAG_MESSAGE_ACK_CODE_LEN)
+            }
+            PayloadType::DiagnosticMessageNack => {
+                AllowedLength::AtLeast(DIAG_ADDRESSES_LEN + DOIP_DIAG_MESSAGE_NACK_CODE_LEN)
+            }
+        }
+    }
+
+    pub(crate) fn contains(self, len: usize) -> bool {
+        match self {
+            AllowedLength::Exact(exact) => len == exact,
+            AllowedLength::Optional(short, long) => len == short || len == long,
+            AllowedLength::AtLeast(min) => len >= min,
+        }
+    }
+
+    pub(crate) fn min(self) -> usize {
+        match self {
+            AllowedLength::Exact(min)
+            | AllowedLength::Optional(min, _)
+            | AllowedLength::AtLeast(min) => min,
+        }
+    }
+}
+
+/// Returns the offset of the field a payload codec failed on because it holds
+/// an unknown value, if the error is of that kind.
+fn value_offset(payload_type: PayloadType, err: &DecodeError) -> Option<usize> {
+    match (payload_type, err) {
+        (PayloadType::GenericNack, DecodeError::InvalidNackCode)
+        | (PayloadType::EntityStatusResponse, DecodeError::InvalidNodeType)
+        | (PayloadType::PowerInformationResponse, DecodeError::InvalidPowerMode) => {
+            Some(DOIP_HEADER_LEN)
+        }
+        (PayloadType::VehicleAnnouncementMessage, DecodeError::InvalidActionCode) => {
+            Some(DOIP_VEHICLE_ANNOUNCEMENT_ACTION_OFFSET)
+        }
+        (PayloadType::VehicleAnnouncementMessage, DecodeError::InvalidSyncStatus) => {
+            Some(DOIP_VEHICLE_ANNOUNCEMENT_SYNC_OFFSET)
+        }
+        (PayloadType::RoutingActivationRequest, DecodeError::InvalidActivationType) => {
+            Some(DOIP_ROUTING_ACTIVATION_REQ_TYPE_OFFSET)
+        }
+        (PayloadType::RoutingActivationResponse, DecodeError::InvalidActivationCode) => {
+            Some(DOIP_ROUTING_ACTIVATION_RES_CODE_OFFSET)
+        }
+        (PayloadType::DiagnosticMessageAck, DecodeError::InvalidDiagnosticAckCode) => {
+            Some(DOIP_DIAG_MESSAGE_ACK_CODE_OFFSET)
+        }
+        (PayloadType::DiagnosticMessageNack, DecodeError::InvalidDiagnosticNackCode) => {
+            Some(DOIP_DIAG_MESSAGE_NACK_CODE_OFFSET)
+        }
+        _ => None,
+    }
+}
 
-    fn decode(
+/// Checks the announced payload length against the lengths ISO 13400-2 allows
+/// for the payload type, including optional fields.
+fn is_allowed_length(payload_type: PayloadType, payload_length: u32) -> bool {
+    AllowedLength::of(payload_type).contains(payload_length as usize)
+}
+
+/// Returns the length of the frame at the start of `src`, including its header,
+/// as announced in the header. `src` must contain at least a full header.
+///
+/// Fails with `DecodeError::MessageTooLarge` if the frame length does not fit
+/// into a `usize`, which can happen on 32-bit targets.
+pub(crate) fn frame_length(src: &[u8]) -> Result<usize, DecodeError> {
+    let payload_length = u32::from_be_bytes(
+        src[DOIP_LENGTH_OFFSET..DOIP_LENGTH_OFFSET + DOIP_LENGTH_LEN]
+            .try_into()
+            .expect("Slice is always the correct length"),
+    );
+
+    usize::try_from(payload_length)
+        .ok()
+        .and_then(|payload_length| DOIP_HEADER_LEN.checked_add(payload_length))
+        .ok_or(DecodeError::MessageTooLarge)
+}
+
+/// Finds the offset of the next possible header start, skipping the first byte
+/// as it is known to start a malformed message.
+///
+/// A trailing protocol version whose inverse has not been received yet counts
+/// as a possible header start.
+fn find_header_start(src: &[u8]) -> usize {
+    (1..src.len())
+        .find(|&i| {
+            let known_version = ProtocolVersion::from_bytes(&src[i..=i]).is_some();
+
+            match src.get(i + 1) {
+                Some(&inverse) => known_version && validate_protocol(src[i], inverse).is_some(),
+                None => known_version,
+            }
+        })
+        .unwrap_or(src.len())
+}
+
+#[cfg(feature = "std")]
+impl DoipCodec {
+    /// Decodes the next frame of a stream and consumes it, applying the
+    /// recovery policy if it fails to decode.
+    pub(crate) fn decode_stream(
         &mut self,
-        src: &mut tokio_util::bytes::BytesMut,
-    ) -> Result<Option<Self::Item>, Self::Error> {
-        let decoded = DoipCodec {}.decode_from_bytes(src);
+        src: &mut BytesMut,
+    ) -> Result<Option<DoipFrame>, DetailedDecodeError> {
+        if self.discard_remaining > 0 {
+            self.discard(src);
 
-        if let Err(DecodeError::TooShort) = decoded {
-            return Ok(None);
+            if self.discard_remaining > 0 {
+                return Ok(None);
+            }
         }
 
-        let decoded = decoded?.inspect(|item| {
-            let decoded_length = item.header.payload_length as usize + DOIP_HEADER_LEN;
-            let advance_length = if src.remaining() >= decoded_length {
-                decoded_length
-            } else {
-                src.len()
-            };
-            src.advance(advance_length);
-        });
+        let decoded = match self.decode_frame(src) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                if matches!(
+                    err.kind(),
+                    DecodeError::MessageTooLarge | DecodeError::OutOfMemory
+                ) {
+                    // Oversized messages are always dropped, regardless of the
+                    // recovery policy, as required by ISO 13400-2.
+                    self.skip_frame(src);
+                } else {
+                    self.recover(src, err.kind());
+                }
+
+                return Err(err);
+            }
+        };
+
+        if decoded.is_some() {
+            src.advance(frame_length(src).expect("Frame length has been checked while decoding"));
+        }
+
+        self.check_decoded(decoded.as_ref());
 
         Ok(decoded)
     }
 }
 
+#[cfg(feature = "std")]
+impl tokio_util::codec::Decoder for DoipCodec {
+    type Item = DoipFrame;
+    type Error = DecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_stream(src)
+            .map_err(DetailedDecodeError::into_kind)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tokio_util::codec::Decoder;
 
+    use crate::{DecodeError, DoipFrame, RawMessage, RecoveryPolicy};
+
+    static INVALID_PAYLOAD_TYPE_THEN_ALIVE_CHECK: [u8; 18] = [
+        0x02, 0xfd, 0x90, 0x42, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x02, 0xfd, 0x00, 0x07, 0x00,
+        0x00, 0x00, 0x00,
+    ];
+
     #[test]
     fn test_decode() {
         let payload = vec![
             0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x0b, 0x11, 0x06, 0x0f, 0x0d, 0x6a, 0xf0,
             0x00, 0x00, 0x00, 0x00, 0x01,
         ];
-        let mut codec = super::DoipCodec {};
+        let mut codec = super::DoipCodec::new();
         let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
         let result = codec.decode(&mut bytes);
         assert!(result.is_ok());
@@ -117,4 +559,405 @@ mod tests {
         let result_incomplete = codec.decode(&mut bytes_incomplete);
         assert!(result_incomplete.is_ok());
     }
+
+    #[test]
+    fn test_frame_length_overflow() {
+        let header = [0x02, 0xfd, 0x80, 0x01, 0xff, 0xff, 0xff, 0xff];
+        let res = super::frame_length(&header);
+
+        if usize::BITS > u32::BITS {
+            assert_eq!(res.unwrap(), 8 + 0xffff_ffff);
+        } else {
+            assert!(matches!(res, Err(DecodeError::MessageTooLarge)));
+        }
+    }
+
+    #[test]
+    fn test_decode_message_too_large() {
+        let payload = vec![
+            0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x0b, 0x11, 0x06, 0x0f, 0x0d, 0x6a, 0xf0,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut codec = super::DoipCodec::new().with_max_payload_length(4);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::MessageTooLarge)));
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_out_of_memory_discards_across_reads() {
+        let payload = vec![
+            0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x0b, 0x11, 0x06, 0x0f, 0x0d, 0x6a, 0xf0,
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut codec = super::DoipCodec::new().with_available_memory(4);
+        let mut bytes = tokio_util::bytes::BytesMut::from(&payload[..10]);
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::OutOfMemory)));
+        assert!(bytes.is_empty());
+
+        bytes.extend_from_slice(&payload[10..]);
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_recovery_fatal() {
+        let mut codec = super::DoipCodec::new();
+        let mut bytes =
+            tokio_util::bytes::BytesMut::from(&INVALID_PAYLOAD_TYPE_THEN_ALIVE_CHECK[..]);
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadType)));
+        assert_eq!(bytes.len(), INVALID_PAYLOAD_TYPE_THEN_ALIVE_CHECK.len());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadType)));
+    }
+
+    #[test]
+    fn test_decode_recovery_skip_frame() {
+        let mut codec = super::DoipCodec::new().with_recovery_policy(RecoveryPolicy::SkipFrame);
+        let mut bytes =
+            tokio_util::bytes::BytesMut::from(&INVALID_PAYLOAD_TYPE_THEN_ALIVE_CHECK[..]);
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadType)));
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_recovery_skip_frame_invalid_header() {
+        let mut codec = super::DoipCodec::new().with_recovery_policy(RecoveryPolicy::SkipFrame);
+        let mut bytes = tokio_util::bytes::BytesMut::from(
+            &[
+                0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0xfd, 0x00, 0x07, 0x00, 0x00,
+                0x00, 0x00,
+            ][..],
+        );
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::FailedProtocolValidation)));
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_recovery_resync() {
+        let mut codec = super::DoipCodec::new().with_recovery_policy(RecoveryPolicy::Resync);
+        let mut bytes =
+            tokio_util::bytes::BytesMut::from(&INVALID_PAYLOAD_TYPE_THEN_ALIVE_CHECK[..]);
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadType)));
+        assert_eq!(bytes.len(), 8);
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(_))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_find_header_start() {
+        assert_eq!(super::find_header_start(&[0x02, 0xfd, 0x42, 0x02, 0xfd]), 3);
+        assert_eq!(super::find_header_start(&[0x02, 0xfd, 0x42, 0x02]), 3);
+        assert_eq!(super::find_header_start(&[0x02, 0xfd, 0x42, 0x42]), 4);
+    }
+
+    #[test]
+    fn test_decode_raw_pass_through() {
+        let payload = vec![
+            0x02, 0xfd, 0xf0, 0x42, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03, 0x02, 0xfd, 0x00,
+            0x07, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut codec = super::DoipCodec::new().with_raw_pass_through(true);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        let Ok(Some(DoipFrame::Raw(raw))) = result else {
+            panic!("Expected a raw frame, got {result:?}");
+        };
+        assert!(raw.is_oem_specific());
+        assert_eq!(
+            raw,
+            RawMessage {
+                protocol_version: doip_definitions::header::ProtocolVersion::Iso13400_2012,
+                payload_type: 0xf042,
+                payload: vec![0x01, 0x02, 0x03],
+            }
+        );
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_raw_pass_through_incomplete() {
+        let payload = vec![0x02, 0xfd, 0xf0, 0x42, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02];
+        let mut codec = super::DoipCodec::new().with_raw_pass_through(true);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(bytes.len(), payload.len());
+    }
+
+    #[test]
+    fn test_decode_default_version_vehicle_identification() {
+        let payload = vec![0xff, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        let mut codec = super::DoipCodec::new();
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+    }
+
+    #[test]
+    fn test_decode_default_version_rejected() {
+        let payload = vec![0xff, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00];
+        let mut codec = super::DoipCodec::new();
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(
+            result,
+            Err(DecodeError::UnsupportedProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn test_decode_unsupported_version() {
+        let payload = vec![0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00];
+        let mut codec = super::DoipCodec::new()
+            .with_supported_versions(&[doip_definitions::header::ProtocolVersion::Iso13400_2019]);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(
+            result,
+            Err(DecodeError::UnsupportedProtocolVersion)
+        ));
+
+        let payload = vec![0x03, 0xfc, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00];
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+    }
+
+    #[test]
+    fn test_decode_default_version_raw_pass_through() {
+        let payload = vec![0xff, 0x00, 0xf0, 0x42, 0x00, 0x00, 0x00, 0x00];
+        let mut codec = super::DoipCodec::new().with_raw_pass_through(true);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(
+            result,
+            Err(DecodeError::UnsupportedProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn test_decode_strict_length() {
+        let payload = vec![
+            0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0xfd, 0x40, 0x04, 0x00,
+            0x00, 0x00, 0x01, 0x00,
+        ];
+        let mut codec = super::DoipCodec::new()
+            .with_strict_length(true)
+            .with_recovery_policy(RecoveryPolicy::SkipFrame);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadLength)));
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_strict_length_power_information_response() {
+        let payload = vec![
+            0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+        let result = super::DoipCodec::new().decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+        let result = super::DoipCodec::new()
+            .with_strict_length(true)
+            .decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPayloadLength)));
+    }
+
+    #[test]
+    fn test_is_allowed_length() {
+        use doip_definitions::header::PayloadType;
+
+        use super::is_allowed_length;
+
+        assert!(is_allowed_length(PayloadType::AliveCheckRequest, 0));
+        assert!(!is_allowed_length(PayloadType::AliveCheckRequest, 1));
+        assert!(is_allowed_length(PayloadType::RoutingActivationRequest, 7));
+        assert!(is_allowed_length(PayloadType::RoutingActivationRequest, 11));
+        assert!(!is_allowed_length(PayloadType::RoutingActivationRequest, 9));
+        assert!(is_allowed_length(PayloadType::EntityStatusResponse, 3));
+        assert!(!is_allowed_length(PayloadType::EntityStatusResponse, 5));
+        assert!(!is_allowed_length(PayloadType::DiagnosticMessage, 4));
+        assert!(is_allowed_length(PayloadType::DiagnosticMessage, 5));
+        assert!(is_allowed_length(PayloadType::DiagnosticMessageAck, 5));
+        assert!(is_allowed_length(PayloadType::DiagnosticMessageNack, 9));
+    }
+
+    #[test]
+    fn test_decode_partial_diagnostic_message() {
+        let payload = [
+            0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x00, 0x10, 0x01, 0x22, 0xf1,
+            0x90,
+        ];
+        let mut codec = super::DoipCodec::new();
+        let mut bytes = tokio_util::bytes::BytesMut::new();
+
+        for byte in &payload[..payload.len() - 1] {
+            bytes.extend_from_slice(&[*byte]);
+            assert!(matches!(codec.decode(&mut bytes), Ok(None)));
+        }
+
+        bytes.extend_from_slice(&payload[payload.len() - 1..]);
+        let Ok(Some(DoipFrame::Message(msg))) = codec.decode(&mut bytes) else {
+            panic!("Expected a complete diagnostic message");
+        };
+
+        assert!(matches!(
+            msg.payload,
+            doip_definitions::payload::DoipPayload::DiagnosticMessage(ref diag)
+                if diag.message == [0x22, 0xf1, 0x90]
+        ));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_diagnostic_message_length_too_short() {
+        let payload = vec![
+            0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x02, 0x0e, 0x00, 0x02, 0xfd, 0x00, 0x07,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut codec = super::DoipCodec::new().with_recovery_policy(RecoveryPolicy::SkipFrame);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::TooShort)));
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Ok(Some(DoipFrame::Message(_)))));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_decode_lenient_unknown_value() {
+        let payload = vec![0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01, 0x7f];
+
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+        let result = super::DoipCodec::new().decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::InvalidPowerMode)));
+
+        let mut codec = super::DoipCodec::new().with_lenient_decoding(true);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+        let result = codec.decode(&mut bytes);
+
+        let Ok(Some(frame @ DoipFrame::Raw(_))) = result else {
+            panic!("Expected a raw frame, got {result:?}");
+        };
+        assert!(bytes.is_empty());
+
+        let mut dst = tokio_util::bytes::BytesMut::new();
+        tokio_util::codec::Encoder::encode(&mut codec, frame, &mut dst).unwrap();
+        assert_eq!(*dst, payload);
+    }
+
+    #[test]
+    fn test_decode_lenient_round_trip() {
+        let mut announcement = [0u8; 33];
+        announcement[31] = 0x7f;
+        let mut announcement_sync = [0u8; 33];
+        announcement_sync[32] = 0x7f;
+
+        let payloads: [(u16, &[u8], DecodeError); 9] = [
+            (0x0000, &[0x7f], DecodeError::InvalidNackCode),
+            (0x0004, &announcement[..32], DecodeError::InvalidActionCode),
+            (0x0004, &announcement_sync, DecodeError::InvalidSyncStatus),
+            (
+                0x0005,
+                &[0x0e, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x00],
+                DecodeError::InvalidActivationType,
+            ),
+            (
+                0x0006,
+                &[0x0e, 0x00, 0x10, 0x00, 0x7f, 0x00, 0x00, 0x00, 0x00],
+                DecodeError::InvalidActivationCode,
+            ),
+            (0x4002, &[0x7f, 0x04, 0x01], DecodeError::InvalidNodeType),
+            (0x4004, &[0x7f], DecodeError::InvalidPowerMode),
+            (
+                0x8002,
+                &[0x10, 0x01, 0x0e, 0x00, 0x7f],
+                DecodeError::InvalidDiagnosticAckCode,
+            ),
+            (
+                0x8003,
+                &[0x10, 0x01, 0x0e, 0x00, 0x7f],
+                DecodeError::InvalidDiagnosticNackCode,
+            ),
+        ];
+
+        for (payload_type, payload, err) in payloads {
+            let mut message = vec![0x02, 0xfd];
+            message.extend_from_slice(&payload_type.to_be_bytes());
+            message.extend_from_slice(&u32::try_from(payload.len()).unwrap().to_be_bytes());
+            message.extend_from_slice(payload);
+
+            let mut bytes = tokio_util::bytes::BytesMut::from(message.as_slice());
+            let result = super::DoipCodec::new().decode(&mut bytes);
+            assert_eq!(result.unwrap_err().to_string(), err.to_string());
+
+            let mut codec = super::DoipCodec::new().with_lenient_decoding(true);
+            let mut bytes = tokio_util::bytes::BytesMut::from(message.as_slice());
+            let result = codec.decode(&mut bytes);
+
+            let Ok(Some(frame @ DoipFrame::Raw(_))) = result else {
+                panic!("Expected a raw frame for {err:?}, got {result:?}");
+            };
+
+            let mut dst = tokio_util::bytes::BytesMut::new();
+            tokio_util::codec::Encoder::encode(&mut codec, frame, &mut dst).unwrap();
+            assert_eq!(*dst, message, "Round trip failed for {err:?}");
+        }
+    }
+
+    #[test]
+    fn test_decode_lenient_keeps_other_errors() {
+        let payload = vec![0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x02, 0x0e, 0x00];
+        let mut codec = super::DoipCodec::new().with_lenient_decoding(true);
+        let mut bytes = tokio_util::bytes::BytesMut::from(payload.as_slice());
+
+        let result = codec.decode(&mut bytes);
+        assert!(matches!(result, Err(DecodeError::TooShort)));
+    }
 }
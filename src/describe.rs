@@ -0,0 +1,221 @@
+use doip_definitions::{
+    header::{PayloadType, ProtocolVersion},
+    payload::{
+        ActionCode, ActivationCode, ActivationType, DiagnosticAckCode, DiagnosticNackCode,
+        NackCode, NodeType, PowerMode, SyncStatus,
+    },
+};
+
+/// Description of the values ISO 13400-2 leaves reserved.
+pub(crate) const RESERVED: &str = "Reserved by ISO 13400";
+
+/// Description of the values ISO 13400-2 leaves to the vehicle manufacturer.
+pub(crate) const VM_SPECIFIC: &str = "VM-specific";
+
+/// Describes a header or payload value in the words of ISO 13400-2, e.g. for
+/// printing traffic in logs.
+///
+/// Implemented for the enums of the `doip-definitions` crate, which cannot
+/// implement `Display` themselves.
+pub trait Describe {
+    /// Returns the description of the value.
+    fn description(&self) -> &'static str;
+}
+
+impl Describe for ProtocolVersion {
+    fn description(&self) -> &'static str {
+        match self {
+            ProtocolVersion::ReservedVer => RESERVED,
+            ProtocolVersion::Iso13400_2010 => "ISO 13400-2:2010",
+            ProtocolVersion::Iso13400_2012 => "ISO 13400-2:2012",
+            ProtocolVersion::Iso13400_2019 => "ISO 13400-2:2019",
+            ProtocolVersion::Iso13400_2019Amd1 => "ISO 13400-2:2019/Amd1",
+            ProtocolVersion::DefaultValue => {
+                "Default value for vehicle identification request messages"
+            }
+        }
+    }
+}
+
+impl Describe for PayloadType {
+    fn description(&self) -> &'static str {
+        match self {
+            PayloadType::GenericNack => "Generic DoIP header negative acknowledge",
+            PayloadType::VehicleIdentificationRequest => "Vehicle identification request",
+            PayloadType::VehicleIdentificationRequestEid => {
+                "Vehicle identification request with EID"
+            }
+            PayloadType::VehicleIdentificationRequestVin => {
+                "Vehicle identification request with VIN"
+            }
+            PayloadType::VehicleAnnouncementMessage => {
+                "Vehicle announcement message/vehicle identification response"
+            }
+            PayloadType::RoutingActivationRequest => "Routing activation request",
+            PayloadType::RoutingActivationResponse => "Routing activation response",
+            PayloadType::AliveCheckRequest => "Alive check request",
+            PayloadType::AliveCheckResponse => "Alive check response",
+            PayloadType::EntityStatusRequest => "DoIP entity status request",
+            PayloadType::EntityStatusResponse => "DoIP entity status response",
+            PayloadType::PowerInformationRequest => "Diagnostic power mode information request",
+            PayloadType::PowerInformationResponse => "Diagnostic power mode information response",
+            PayloadType::DiagnosticMessage => "Diagnostic message",
+            PayloadType::DiagnosticMessageAck => "Diagnostic message positive acknowledgement",
+            PayloadType::DiagnosticMessageNack => "Diagnostic message negative acknowledgement",
+        }
+    }
+}
+
+impl Describe for NackCode {
+    fn description(&self) -> &'static str {
+        match self {
+            NackCode::IncorrectPatternFormat => "Incorrect pattern format",
+            NackCode::UnknownPayloadType => "Unknown payload type",
+            NackCode::MessageTooLarge => "Message too large",
+            NackCode::OutOfMemory => "Out of memory",
+            NackCode::InvalidPayloadLength => "Invalid payload length",
+        }
+    }
+}
+
+impl Describe for ActionCode {
+    fn description(&self) -> &'static str {
+        match self {
+            ActionCode::NoFurtherActionRequired => "No further action required",
+            ActionCode::RoutingActivationRequired => {
+                "Routing activation required to initiate central security"
+            }
+            _ => RESERVED,
+        }
+    }
+}
+
+impl Describe for SyncStatus {
+    fn description(&self) -> &'static str {
+        match self {
+            SyncStatus::VinGidSynchronized => "VIN and/or GID are synchronized",
+            SyncStatus::VinGidNotSynchronised => "Incomplete: VIN and GID are not synchronized",
+            _ => RESERVED,
+        }
+    }
+}
+
+impl Describe for ActivationType {
+    fn description(&self) -> &'static str {
+        match self {
+            ActivationType::Default => "Default",
+            ActivationType::WwhObd => "WWH-OBD",
+            ActivationType::CentralSecurity => "Central security",
+        }
+    }
+}
+
+impl Describe for ActivationCode {
+    fn description(&self) -> &'static str {
+        match self {
+            ActivationCode::DeniedUnknownSourceAddress => {
+                "Routing activation denied due to unknown source address"
+            }
+            ActivationCode::DeniedTCPSocketsFull => {
+                "Routing activation denied because all concurrently supported TCP sockets are registered and active"
+            }
+            ActivationCode::DeniedTCPSocketAlreadyConnected => {
+                "Routing activation denied because an SA different from the table connection entry was received on the already activated TCP socket"
+            }
+            ActivationCode::DeniedSourceIsAlreadyActive => {
+                "Routing activation denied because the SA is already registered and active on a different TCP socket"
+            }
+            ActivationCode::DeniedMissingAuthentication => {
+                "Routing activation denied due to missing authentication"
+            }
+            ActivationCode::DeniedRejectedConfirmation => {
+                "Routing activation denied due to rejected confirmation"
+            }
+            ActivationCode::DeniedUnsupportedRoutingActivationType => {
+                "Routing activation denied due to unsupported routing activation type"
+            }
+            ActivationCode::DeniedRequestEncryptedTLSConnection => {
+                "Routing activation denied due to request for encrypted connection via TLS"
+            }
+            ActivationCode::SuccessfullyActivated => "Routing successfully activated",
+            ActivationCode::ActivatedConfirmationRequired => {
+                "Routing will be activated; confirmation required"
+            }
+            _ => RESERVED,
+        }
+    }
+}
+
+impl Describe for NodeType {
+    fn description(&self) -> &'static str {
+        match self {
+            NodeType::DoipGateway => "DoIP gateway",
+            NodeType::DoipNode => "DoIP node",
+        }
+    }
+}
+
+impl Describe for PowerMode {
+    fn description(&self) -> &'static str {
+        match self {
+            PowerMode::NotReady => "Not ready",
+            PowerMode::Ready => "Ready",
+            PowerMode::NotSupported => "Not supported",
+        }
+    }
+}
+
+impl Describe for DiagnosticAckCode {
+    fn description(&self) -> &'static str {
+        match self {
+            DiagnosticAckCode::Acknowledged => "Routing confirmation acknowledge",
+        }
+    }
+}
+
+impl Describe for DiagnosticNackCode {
+    fn description(&self) -> &'static str {
+        match self {
+            DiagnosticNackCode::InvalidSourceAddress => "Invalid source address",
+            DiagnosticNackCode::UnknownTargetAddress => "Unknown target address",
+            DiagnosticNackCode::DiagnosticMessageTooLarge => "Diagnostic message too large",
+            DiagnosticNackCode::OutOfMemory => "Out of memory",
+            DiagnosticNackCode::TargetUnreachable => "Target unreachable",
+            DiagnosticNackCode::UnknownNetwork => "Unknown network",
+            DiagnosticNackCode::TransportProtocolError => "Transport protocol error",
+            _ => RESERVED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::{
+        header::{PayloadType, ProtocolVersion},
+        payload::{ActionCode, DiagnosticNackCode},
+    };
+
+    use super::{Describe, RESERVED};
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            ProtocolVersion::Iso13400_2012.description(),
+            "ISO 13400-2:2012"
+        );
+        assert_eq!(
+            PayloadType::DiagnosticMessage.description(),
+            "Diagnostic message"
+        );
+    }
+
+    #[test]
+    fn test_description_reserved() {
+        assert_eq!(ProtocolVersion::ReservedVer.description(), RESERVED);
+        assert_eq!(ActionCode::ReservedByIso13400_01.description(), RESERVED);
+        assert_eq!(
+            DiagnosticNackCode::ReservedByIso13400_00.description(),
+            RESERVED
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use doip_definitions::{
+    header::ProtocolVersion,
+    payload::{
+        ActionCode, DoipPayload, SyncStatus, VehicleIdentificationRequest,
+        VehicleIdentificationRequestEid, VehicleIdentificationRequestVin,
+    },
+};
+use tokio::{
+    net::UdpSocket,
+    time::{timeout_at, Instant},
+};
+
+use crate::{ClientError, DoipCodec, DoipFrame, LogicalAddress, A_DOIP_CTRL};
+
+/// UDP and TCP port of `DoIP` entities.
+pub const DOIP_PORT: u16 = 13400;
+
+/// Largest datagram read while collecting vehicle announcements.
+const MAX_DATAGRAM_LEN: usize = 1500;
+
+/// Selects the entities which are asked to identify themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiscoveryFilter {
+    /// Every entity, using a `VehicleIdentificationRequest`.
+    #[default]
+    All,
+
+    /// Entities with the given EID, using a `VehicleIdentificationRequestEid`.
+    Eid([u8; 6]),
+
+    /// Entities of the vehicle with the given VIN, using a
+    /// `VehicleIdentificationRequestVin`.
+    Vin([u8; 17]),
+}
+
+impl DiscoveryFilter {
+    fn payload(self) -> DoipPayload {
+        match self {
+            DiscoveryFilter::All => {
+                DoipPayload::VehicleIdentificationRequest(VehicleIdentificationRequest {})
+            }
+            DiscoveryFilter::Eid(eid) => {
+                DoipPayload::VehicleIdentificationRequestEid(VehicleIdentificationRequestEid {
+                    eid,
+                })
+            }
+            DiscoveryFilter::Vin(vin) => {
+                DoipPayload::VehicleIdentificationRequestVin(VehicleIdentificationRequestVin {
+                    vin,
+                })
+            }
+        }
+    }
+}
+
+/// Configuration of a vehicle discovery, see [`discover`].
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    bind_address: SocketAddr,
+    target: SocketAddr,
+    filter: DiscoveryFilter,
+    protocol_version: ProtocolVersion,
+    window: Duration,
+}
+
+impl DiscoveryConfig {
+    /// Creates a configuration broadcasting a request to every entity on port
+    /// 13400 and collecting announcements for `A_DoIP_Ctrl`.
+    #[must_use]
+    pub fn new() -> Self {
+        DiscoveryConfig {
+            bind_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            target: SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), DOIP_PORT),
+            filter: DiscoveryFilter::All,
+            protocol_version: ProtocolVersion::DefaultValue,
+            window: A_DOIP_CTRL,
+        }
+    }
+
+    /// Sets the local address the request is sent from, e.g. to select a
+    /// network interface.
+    #[must_use]
+    pub fn with_bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Sets the address the request is sent to, a broadcast address or a
+    /// single entity.
+    #[must_use]
+    pub fn with_target(mut self, target: SocketAddr) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Sets the entities which are asked to identify themselves.
+    #[must_use]
+    pub fn with_filter(mut self, filter: DiscoveryFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the protocol version of the request. Defaults to `0xFF`, which
+    /// every entity accepts.
+    #[must_use]
+    pub fn with_protocol_version(mut self, protocol_version: ProtocolVersion) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// Sets how long announcements are collected after sending the request.
+    #[must_use]
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Returns the local address the request is sent from.
+    #[must_use]
+    pub fn bind_address(&self) -> SocketAddr {
+        self.bind_address
+    }
+
+    /// Returns the address the request is sent to.
+    #[must_use]
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Returns the entities which are asked to identify themselves.
+    #[must_use]
+    pub fn filter(&self) -> DiscoveryFilter {
+        self.filter
+    }
+
+    /// Returns the protocol version of the request.
+    #[must_use]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Returns how long announcements are collected.
+    #[must_use]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `DoIP` entity which answered a vehicle discovery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredEntity {
+    /// IP address the announcement was received from.
+    pub ip: IpAddr,
+
+    /// Logical address of the entity.
+    pub logical_address: LogicalAddress,
+
+    /// Vehicle identification number.
+    pub vin: [u8; 17],
+
+    /// Entity identification, unique per entity.
+    pub eid: [u8; 6],
+
+    /// Group identification of the vehicle.
+    pub gid: [u8; 6],
+
+    /// Further action required by the tester.
+    pub further_action: ActionCode,
+
+    /// VIN/GID synchronisation status, only reported by gateways.
+    pub vin_gid_sync: Option<SyncStatus>,
+}
+
+/// Sends a vehicle identification request over UDP and collects the
+/// announcements received within the configured window.
+///
+/// Entities are returned in the order their first announcement arrived,
+/// repeated announcements of the same EID are dropped. Datagrams which do not
+/// hold a valid vehicle announcement are ignored.
+///
+/// # Errors
+///
+/// Returns `ClientError::Encode` if the identification request could not be
+/// encoded, and `ClientError::IOError` if the socket could not be set up, the request could
+/// not be sent or receiving a response failed. Responses that fail to decode
+/// or are not vehicle announcements are skipped, so no other error is
+/// returned.
+pub async fn discover(config: &DiscoveryConfig) -> Result<Vec<DiscoveredEntity>, ClientError> {
+    let socket = UdpSocket::bind(config.bind_address).await?;
+    socket.set_broadcast(true)?;
+
+    let mut codec = DoipCodec::new();
+    let mut buf = BytesMut::new();
+    let request = DoipCodec::build_message(config.protocol_version, config.filter.payload())?;
+    tokio_util::codec::Encoder::encode(&mut codec, request, &mut buf)?;
+    socket.send_to(&buf, config.target).await?;
+
+    let deadline = Instant::now() + config.window;
+    let mut datagram = [0; MAX_DATAGRAM_LEN];
+    let mut entities: Vec<DiscoveredEntity> = Vec::new();
+
+    while let Ok(res) = timeout_at(deadline, socket.recv_from(&mut datagram)).await {
+        let (len, peer) = res?;
+
+        let Ok(DoipFrame::Message(message)) = codec.decode_datagram(&datagram[..len]) else {
+            continue;
+        };
+        let DoipPayload::VehicleAnnouncementMessage(announcement) = message.payload else {
+            continue;
+        };
+
+        if entities.iter().any(|entity| entity.eid == announcement.eid) {
+            continue;
+        }
+
+        entities.push(DiscoveredEntity {
+            ip: peer.ip(),
+            logical_address: LogicalAddress::from(announcement.logical_address),
+            vin: announcement.vin,
+            eid: announcement.eid,
+            gid: announcement.gid,
+            further_action: announcement.further_action,
+            vin_gid_sync: announcement.vin_gid_sync,
+        });
+    }
+
+    Ok(entities)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use doip_definitions::{
+        header::ProtocolVersion,
+        payload::{ActionCode, DoipPayload, VehicleAnnouncementMessage},
+    };
+    use tokio::net::UdpSocket;
+
+    use crate::{discover, DiscoveryConfig, DiscoveryFilter, DoipCodec, DoipFrame, LogicalAddress};
+
+    fn announcement(logical_address: [u8; 2], eid: [u8; 6]) -> BytesMut {
+        let message = DoipCodec::build_message(
+            ProtocolVersion::Iso13400_2012,
+            DoipPayload::VehicleAnnouncementMessage(VehicleAnnouncementMessage {
+                vin: *b"WVWZZZ1JZXW000001",
+                logical_address,
+                eid,
+                gid: [0; 6],
+                further_action: ActionCode::RoutingActivationRequired,
+                vin_gid_sync: None,
+            }),
+        )
+        .unwrap();
+
+        let mut buf = BytesMut::new();
+        tokio_util::codec::Encoder::encode(&mut DoipCodec::new(), message, &mut buf).unwrap();
+        buf
+    }
+
+    #[tokio::test]
+    async fn test_discover_loopback() {
+        let responder = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = DiscoveryConfig::new()
+            .with_bind_address("127.0.0.1:0".parse().unwrap())
+            .with_target(responder.local_addr().unwrap())
+            .with_filter(DiscoveryFilter::Eid([0, 0, 0, 0, 0, 1]))
+            .with_window(Duration::from_millis(200));
+
+        let responder = tokio::spawn(async move {
+            let mut datagram = [0; 64];
+            let (len, tester) = responder.recv_from(&mut datagram).await.unwrap();
+
+            let frame = DoipCodec::new().decode_datagram(&datagram[..len]).unwrap();
+            let DoipFrame::Message(request) = frame else {
+                panic!("Expected a vehicle identification request");
+            };
+            assert_eq!(
+                request.header.protocol_version,
+                ProtocolVersion::DefaultValue
+            );
+            assert!(matches!(
+                request.payload,
+                DoipPayload::VehicleIdentificationRequestEid(req) if req.eid == [0, 0, 0, 0, 0, 1]
+            ));
+
+            let replies = [
+                announcement([0x10, 0x00], [0, 0, 0, 0, 0, 1]),
+                BytesMut::from(&[0x02, 0xfd, 0x00, 0x04][..]),
+                announcement([0x10, 0x00], [0, 0, 0, 0, 0, 1]),
+                announcement([0x20, 0x00], [0, 0, 0, 0, 0, 2]),
+            ];
+            for reply in replies {
+                responder.send_to(&reply, tester).await.unwrap();
+            }
+        });
+
+        let entities = discover(&config).await.unwrap();
+        responder.await.unwrap();
+
+        assert_eq!(entities.len(), 2);
+        assert_eq!(
+            entities[0].ip,
+            "127.0.0.1".parse::<std::net::IpAddr>().unwrap()
+        );
+        assert_eq!(entities[0].logical_address, LogicalAddress::new(0x1000));
+        assert_eq!(entities[0].vin, *b"WVWZZZ1JZXW000001");
+        assert_eq!(
+            entities[0].further_action,
+            ActionCode::RoutingActivationRequired
+        );
+        assert_eq!(entities[1].eid, [0, 0, 0, 0, 0, 2]);
+    }
+}
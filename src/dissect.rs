@@ -0,0 +1,561 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+use doip_definitions::{
+    definitions::{
+        DOIP_HEADER_LEN, DOIP_INV_VERSION_OFFSET, DOIP_LENGTH_LEN, DOIP_LENGTH_OFFSET,
+        DOIP_TYPE_LEN, DOIP_TYPE_OFFSET, DOIP_VERSION_OFFSET,
+    },
+    header::{PayloadType, ProtocolVersion},
+    message::DoipMessage,
+    payload::{
+        ActionCode, ActivationCode, ActivationType, DiagnosticAckCode, DiagnosticNackCode,
+        NackCode, NodeType, PowerMode, SyncStatus,
+    },
+};
+
+use crate::{
+    describe::{RESERVED, VM_SPECIFIC},
+    DecodeError, Describe, DoipCodec, DoipMessageView, EncodeError, Encoder, FromBytes,
+    LogicalAddress,
+};
+
+/// A single field of a dissected message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DissectedField {
+    /// Name of the field as used by ISO 13400-2.
+    pub name: &'static str,
+
+    /// Offset of the field from the start of the message, header included.
+    pub offset: usize,
+
+    /// Raw bytes of the field.
+    pub raw: Vec<u8>,
+
+    /// Decoded meaning of the field.
+    pub value: String,
+}
+
+/// A message broken down into its header and payload fields, e.g. for printing
+/// traffic in logs.
+///
+/// Built from the bytes of a message with [`Dissection::new`], or from a
+/// `DoipMessage` with `Dissection::try_from`, which encodes it first.
+///
+/// The [`Display`](fmt::Display) implementation renders an indented text tree,
+/// [`Dissection::to_json`] a machine-readable form. Codes unknown to this crate
+/// are rendered with their raw value instead of failing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dissection {
+    /// Description of the payload type.
+    pub payload_type: &'static str,
+
+    /// Fields of the generic header.
+    pub header: Vec<DissectedField>,
+
+    /// Fields of the payload, followed by any bytes which could not be
+    /// attributed to a field.
+    pub payload: Vec<DissectedField>,
+}
+
+/// How the raw bytes of a field are rendered.
+#[derive(Clone, Copy)]
+enum Kind {
+    Number,
+    Hex,
+    Address,
+    Text,
+    Identifier,
+    Code(fn(u8) -> &'static str),
+    Data,
+}
+
+/// Layout of a payload field.
+struct FieldSpec {
+    name: &'static str,
+    len: Option<usize>,
+    optional: bool,
+    kind: Kind,
+}
+
+const fn field(name: &'static str, len: usize, kind: Kind) -> FieldSpec {
+    FieldSpec {
+        name,
+        len: Some(len),
+        optional: false,
+        kind,
+    }
+}
+
+const fn optional(name: &'static str, len: usize, kind: Kind) -> FieldSpec {
+    FieldSpec {
+        name,
+        len: Some(len),
+        optional: true,
+        kind,
+    }
+}
+
+const fn remaining(name: &'static str, optional: bool) -> FieldSpec {
+    FieldSpec {
+        name,
+        len: None,
+        optional,
+        kind: Kind::Data,
+    }
+}
+
+const SOURCE_ADDRESS: FieldSpec = field("Source Address", 2, Kind::Address);
+const TARGET_ADDRESS: FieldSpec = field("Target Address", 2, Kind::Address);
+const RESERVED_ISO: FieldSpec = field("Reserved by ISO 13400", 4, Kind::Data);
+const RESERVED_OEM: FieldSpec = optional("Reserved for OEM-specific use", 4, Kind::Data);
+
+impl Dissection {
+    /// Dissects a buffer holding exactly one message.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DecodeError` if the buffer does not hold exactly one message
+    /// with a valid protocol version pattern, see [`DoipMessageView::new`].
+    pub fn new(src: &[u8]) -> Result<Self, DecodeError> {
+        let view = DoipMessageView::new(src)?;
+        let payload_type = view.payload_type_code();
+
+        let header = alloc::vec![
+            dissect_field(
+                src,
+                DOIP_VERSION_OFFSET,
+                1,
+                "Protocol Version",
+                Kind::Code(describe::<ProtocolVersion>)
+            ),
+            dissect_field(
+                src,
+                DOIP_INV_VERSION_OFFSET,
+                1,
+                "Inverse Protocol Version",
+                Kind::Hex
+            ),
+            DissectedField {
+                name: "Payload Type",
+                offset: DOIP_TYPE_OFFSET,
+                raw: src[DOIP_TYPE_OFFSET..DOIP_TYPE_OFFSET + DOIP_TYPE_LEN].to_vec(),
+                value: payload_type_name(payload_type).to_string(),
+            },
+            dissect_field(
+                src,
+                DOIP_LENGTH_OFFSET,
+                DOIP_LENGTH_LEN,
+                "Payload Length",
+                Kind::Number
+            ),
+        ];
+
+        let mut offset = DOIP_HEADER_LEN;
+        let mut payload = Vec::new();
+
+        for spec in payload_layout(payload_type) {
+            let available = src.len() - offset;
+            let len = spec.len.unwrap_or(available);
+
+            if available == 0 && (spec.optional || spec.len.is_none()) {
+                break;
+            }
+
+            if available < len {
+                let mut truncated = dissect_field(src, offset, available, spec.name, Kind::Data);
+                truncated.value = format!("truncated, {available} of {len} bytes");
+                payload.push(truncated);
+                offset += available;
+                break;
+            }
+
+            payload.push(dissect_field(src, offset, len, spec.name, spec.kind));
+            offset += len;
+        }
+
+        if offset < src.len() {
+            payload.push(dissect_field(
+                src,
+                offset,
+                src.len() - offset,
+                "Trailing Data",
+                Kind::Data,
+            ));
+        }
+
+        Ok(Dissection {
+            payload_type: payload_type_name(payload_type),
+            header,
+            payload,
+        })
+    }
+
+    /// Renders the dissection as a JSON object with a `header` and a `payload`
+    /// list of fields.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let fields = |json: &mut String, fields: &[DissectedField]| {
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                let _ = write!(
+                    json,
+                    "{{\"name\":\"{}\",\"offset\":{},\"raw\":\"{}\",\"value\":\"{}\"}}",
+                    field.name,
+                    field.offset,
+                    Hex(&field.raw),
+                    JsonEscaped(&field.value)
+                );
+            }
+        };
+
+        let _ = write!(
+            json,
+            "{{\"payload_type\":\"{}\",\"header\":[",
+            self.payload_type
+        );
+        fields(&mut json, &self.header);
+        json.push_str("],\"payload\":[");
+        fields(&mut json, &self.payload);
+        json.push_str("]}");
+
+        json
+    }
+}
+
+impl TryFrom<&DoipMessage> for Dissection {
+    type Error = EncodeError;
+
+    /// Dissects a message as it would be sent by the encoder.
+    fn try_from(value: &DoipMessage) -> Result<Self, Self::Error> {
+        let mut codec = DoipCodec::new();
+        let mut src = Vec::with_capacity(codec.encoded_len(value));
+        codec.to_bytes(value.clone(), &mut src)?;
+
+        Ok(Dissection::new(&src).expect("The encoder only produces complete messages"))
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "DoIP Message ({})", self.payload_type)?;
+
+        for (name, fields) in [("Header", &self.header), ("Payload", &self.payload)] {
+            writeln!(f, "  {name}")?;
+
+            for field in fields {
+                writeln!(
+                    f,
+                    "    [{:>2}] {}: {} ({})",
+                    field.offset,
+                    field.name,
+                    Hex(&field.raw),
+                    field.value
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn dissect_field(
+    src: &[u8],
+    offset: usize,
+    len: usize,
+    name: &'static str,
+    kind: Kind,
+) -> DissectedField {
+    let raw = &src[offset..offset + len];
+
+    let value = match kind {
+        Kind::Number => raw
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte))
+            .to_string(),
+        Kind::Hex => raw.iter().fold(String::from("0x"), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02X}");
+            hex
+        }),
+        Kind::Address => {
+            let address = LogicalAddress::from([raw[0], raw[1]]);
+            format!("{address}, {}", address.class())
+        }
+        Kind::Text => raw
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect(),
+        Kind::Identifier => raw
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+        Kind::Code(describe) => describe(raw[0]).to_string(),
+        Kind::Data if raw.len() == 1 => "1 byte".to_string(),
+        Kind::Data => format!("{} bytes", raw.len()),
+    };
+
+    DissectedField {
+        name,
+        offset,
+        raw: raw.to_vec(),
+        value,
+    }
+}
+
+fn payload_layout(payload_type: u16) -> Vec<FieldSpec> {
+    match payload_type {
+        0x0000 => alloc::vec![field("NACK Code", 1, Kind::Code(describe::<NackCode>))],
+        0x0002 => alloc::vec![field("EID", 6, Kind::Identifier)],
+        0x0003 => alloc::vec![field("VIN", 17, Kind::Text)],
+        0x0004 => alloc::vec![
+            field("VIN", 17, Kind::Text),
+            field("Logical Address", 2, Kind::Address),
+            field("EID", 6, Kind::Identifier),
+            field("GID", 6, Kind::Identifier),
+            field("Further Action Required", 1, Kind::Code(action_code)),
+            optional("VIN/GID Sync Status", 1, Kind::Code(describe::<SyncStatus>)),
+        ],
+        0x0005 => alloc::vec![
+            SOURCE_ADDRESS,
+            field("Activation Type", 1, Kind::Code(activation_type)),
+            RESERVED_ISO,
+            RESERVED_OEM,
+        ],
+        0x0006 => alloc::vec![
+            field("Logical Address of Tester", 2, Kind::Address),
+            field("Logical Address of DoIP Entity", 2, Kind::Address),
+            field(
+                "Routing Activation Response Code",
+                1,
+                Kind::Code(activation_code)
+            ),
+            RESERVED_ISO,
+            RESERVED_OEM,
+        ],
+        0x0008 => alloc::vec![SOURCE_ADDRESS],
+        0x4002 => alloc::vec![
+            field("Node Type", 1, Kind::Code(describe::<NodeType>)),
+            field("Max. Concurrent TCP Sockets", 1, Kind::Number),
+            field("Currently Open TCP Sockets", 1, Kind::Number),
+            optional("Max. Data Size", 4, Kind::Number),
+        ],
+        0x4004 => alloc::vec![field(
+            "Diagnostic Power Mode",
+            1,
+            Kind::Code(describe::<PowerMode>)
+        )],
+        0x8001 => alloc::vec![
+            SOURCE_ADDRESS,
+            TARGET_ADDRESS,
+            remaining("User Data", false)
+        ],
+        0x8002 => alloc::vec![
+            SOURCE_ADDRESS,
+            TARGET_ADDRESS,
+            field("ACK Code", 1, Kind::Code(describe::<DiagnosticAckCode>)),
+            remaining("Previous Diagnostic Message Data", true),
+        ],
+        0x8003 => alloc::vec![
+            SOURCE_ADDRESS,
+            TARGET_ADDRESS,
+            field("NACK Code", 1, Kind::Code(describe::<DiagnosticNackCode>)),
+            remaining("Previous Diagnostic Message Data", true),
+        ],
+        0x0001 | 0x0007 | 0x4001 | 0x4003 => Vec::new(),
+        _ => alloc::vec![remaining("Data", true)],
+    }
+}
+
+fn payload_type_name(payload_type: u16) -> &'static str {
+    match PayloadType::from_bytes(&payload_type.to_be_bytes()) {
+        Some(payload_type) => payload_type.description(),
+        None if payload_type >= 0xF000 => "Reserved for manufacturer-specific use",
+        None => RESERVED,
+    }
+}
+
+/// Describes a code with the description of its enum, or as reserved if the
+/// enum does not know it.
+fn describe<T: FromBytes + Describe>(value: u8) -> &'static str {
+    T::from_bytes(&[value]).map_or(RESERVED, |code| code.description())
+}
+
+fn action_code(value: u8) -> &'static str {
+    match value {
+        0x11..=0xFF => VM_SPECIFIC,
+        _ => describe::<ActionCode>(value),
+    }
+}
+
+fn activation_type(value: u8) -> &'static str {
+    match value {
+        0xE0..=0xFF => VM_SPECIFIC,
+        _ => describe::<ActivationType>(value),
+    }
+}
+
+fn activation_code(value: u8) -> &'static str {
+    match value {
+        0xE0..=0xFE => VM_SPECIFIC,
+        _ => describe::<ActivationCode>(value),
+    }
+}
+
+/// Renders bytes as space separated hex.
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Escapes a string for use within a JSON string literal.
+struct JsonEscaped<'a>(&'a str);
+
+impl fmt::Display for JsonEscaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::payload::{DiagnosticMessage, DoipPayload};
+
+    use crate::{test_util::message, DecodeError, Dissection};
+
+    static DIAGNOSTIC_MESSAGE: [u8; 15] = [
+        0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x00, 0x10, 0x01, 0x22, 0xf1, 0x90,
+    ];
+
+    #[test]
+    fn test_dissect_text_tree() {
+        let dissection = Dissection::new(&DIAGNOSTIC_MESSAGE).unwrap();
+
+        assert_eq!(
+            dissection.to_string(),
+            "DoIP Message (Diagnostic message)\n\
+             \x20 Header\n\
+             \x20   [ 0] Protocol Version: 02 (ISO 13400-2:2012)\n\
+             \x20   [ 1] Inverse Protocol Version: fd (0xFD)\n\
+             \x20   [ 2] Payload Type: 80 01 (Diagnostic message)\n\
+             \x20   [ 4] Payload Length: 00 00 00 07 (7)\n\
+             \x20 Payload\n\
+             \x20   [ 8] Source Address: 0e 00 (0x0E00, external legacy tester)\n\
+             \x20   [10] Target Address: 10 01 (0x1001, VM-specific ECU)\n\
+             \x20   [12] User Data: 22 f1 90 (3 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_dissect_message() {
+        let item = message(DoipPayload::DiagnosticMessage(DiagnosticMessage {
+            source_address: [0x0e, 0x00],
+            target_address: [0x10, 0x01],
+            message: vec![0x22, 0xf1, 0x90],
+        }));
+
+        let dissection = Dissection::try_from(&item).unwrap();
+
+        assert_eq!(dissection, Dissection::new(&DIAGNOSTIC_MESSAGE).unwrap());
+    }
+
+    #[test]
+    fn test_dissect_json() {
+        let bytes = [0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01, 0x7f];
+
+        let dissection = Dissection::new(&bytes).unwrap();
+
+        assert_eq!(
+            dissection.to_json(),
+            "{\"payload_type\":\"Diagnostic power mode information response\",\"header\":[\
+             {\"name\":\"Protocol Version\",\"offset\":0,\"raw\":\"02\",\"value\":\"ISO 13400-2:2012\"},\
+             {\"name\":\"Inverse Protocol Version\",\"offset\":1,\"raw\":\"fd\",\"value\":\"0xFD\"},\
+             {\"name\":\"Payload Type\",\"offset\":2,\"raw\":\"40 04\",\"value\":\"Diagnostic power mode information response\"},\
+             {\"name\":\"Payload Length\",\"offset\":4,\"raw\":\"00 00 00 01\",\"value\":\"1\"}],\"payload\":[\
+             {\"name\":\"Diagnostic Power Mode\",\"offset\":8,\"raw\":\"7f\",\"value\":\"Reserved by ISO 13400\"}]}"
+        );
+    }
+
+    #[test]
+    fn test_dissect_vehicle_announcement() {
+        let mut bytes = vec![0x02, 0xfd, 0x00, 0x04, 0x00, 0x00, 0x00, 0x20];
+        bytes.extend_from_slice(b"WVWZZZ1JZXW000001");
+        bytes.extend_from_slice(&[0x10, 0x00]);
+        bytes.extend_from_slice(&[0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        bytes.extend_from_slice(&[0x00; 6]);
+        bytes.push(0x10);
+
+        let dissection = Dissection::new(&bytes).unwrap();
+        let values: Vec<_> = dissection
+            .payload
+            .iter()
+            .map(|field| (field.name, field.offset, field.value.as_str()))
+            .collect();
+
+        assert_eq!(
+            values,
+            [
+                ("VIN", 8, "WVWZZZ1JZXW000001"),
+                ("Logical Address", 25, "0x1000, VM-specific ECU"),
+                ("EID", 27, "00:1A:2B:3C:4D:5E"),
+                ("GID", 33, "00:00:00:00:00:00"),
+                (
+                    "Further Action Required",
+                    39,
+                    "Routing activation required to initiate central security"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dissect_truncated_and_trailing() {
+        let bytes = [0x02, 0xfd, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x0e];
+        let dissection = Dissection::new(&bytes).unwrap();
+        assert_eq!(dissection.payload[0].value, "truncated, 1 of 2 bytes");
+
+        let bytes = [0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x0e];
+        let dissection = Dissection::new(&bytes).unwrap();
+        assert_eq!(dissection.payload[0].name, "Trailing Data");
+    }
+
+    #[test]
+    fn test_dissect_incomplete() {
+        let res = Dissection::new(&DIAGNOSTIC_MESSAGE[..12]);
+
+        assert!(matches!(res, Err(DecodeError::TooShort)));
+    }
+}
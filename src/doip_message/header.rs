@@ -1,3 +1,4 @@
+use bytes::BufMut;
 use doip_definitions::{
     definitions::{
         DOIP_HEADER_LEN, DOIP_INV_VERSION_OFFSET, DOIP_LENGTH_LEN, DOIP_LENGTH_OFFSET,
@@ -14,7 +15,15 @@ pub struct HeaderCodec;
 impl Encoder<DoipHeader> for HeaderCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: DoipHeader, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, _item: &DoipHeader) -> usize {
+        DOIP_HEADER_LEN
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: DoipHeader,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let DoipHeader {
             protocol_version,
             inverse_protocol_version,
@@ -31,10 +40,10 @@ impl Encoder<DoipHeader> for HeaderCodec {
             return Err(EncodeError::FailedProtocolValidation);
         }
 
-        dst.extend_from_slice(protocol_version_bytes);
-        dst.extend_from_slice(inverse_protocol_version_bytes);
-        dst.extend_from_slice(payload_type_bytes);
-        dst.extend_from_slice(payload_length_bytes);
+        dst.put_slice(protocol_version_bytes);
+        dst.put_slice(inverse_protocol_version_bytes);
+        dst.put_slice(payload_type_bytes);
+        dst.put_slice(payload_length_bytes);
 
         Ok(())
     }
@@ -112,7 +121,7 @@ impl ToBytes for PayloadType {
     }
 }
 
-fn validate_protocol(proto: u8, inv_proto: u8) -> Option<()> {
+pub(crate) fn validate_protocol(proto: u8, inv_proto: u8) -> Option<()> {
     if !proto == inv_proto {
         Some(())
     } else {
@@ -488,7 +497,7 @@ mod tests {
 
         let bytes = &[0x02, 0x0fd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
         dst.extend_from_slice(bytes);
-        let item = codec.decode_from_bytes(&mut dst);
+        let item = codec.decode_from_bytes(&dst);
 
         assert!(item.is_ok());
         let opt = item.unwrap();
@@ -525,7 +534,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.header.clone(), &mut dst);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_ok());
         let opt = msg.unwrap();
@@ -542,7 +551,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         dst.extend_from_slice(&[0x02, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00]);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_err());
     }
@@ -553,7 +562,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         dst.extend_from_slice(&[0x02, 0xff, 0x00, 0x00, 0x00, 0x00]);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_err());
     }
@@ -564,7 +573,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         dst.extend_from_slice(&[0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_err());
     }
@@ -575,7 +584,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         dst.extend_from_slice(&[0x02, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_err());
     }
@@ -586,7 +595,7 @@ mod tests {
         let mut dst = Vec::<u8>::new();
 
         dst.extend_from_slice(&[0x02, 0xfd, 0x90, 0x42, 0x00, 0x00, 0x00, 0x00]);
-        let msg = codec.decode_from_bytes(&mut dst);
+        let msg = codec.decode_from_bytes(&dst);
 
         assert!(msg.is_err());
     }
@@ -16,6 +16,8 @@ use vehicle_identification_request::VehicleIdentificationRequestCodec;
 use vehicle_identification_request_eid::VehicleIdentificationRequestEidCodec;
 use vehicle_identification_request_vin::VehicleIdentificationRequestVinCodec;
 
+use bytes::BufMut;
+
 use crate::{EncodeError, Encoder};
 
 pub mod alive_check_request;
@@ -41,7 +43,64 @@ pub struct PayloadCodec;
 impl Encoder<DoipPayload> for PayloadCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: DoipPayload, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &DoipPayload) -> usize {
+        match item {
+            DoipPayload::GenericNack(generic_nack) => GenericNackCodec {}.encoded_len(generic_nack),
+            DoipPayload::VehicleIdentificationRequest(vehicle_identification_request) => {
+                VehicleIdentificationRequestCodec {}.encoded_len(vehicle_identification_request)
+            }
+            DoipPayload::VehicleIdentificationRequestEid(vehicle_identification_request_eid) => {
+                VehicleIdentificationRequestEidCodec {}
+                    .encoded_len(vehicle_identification_request_eid)
+            }
+            DoipPayload::VehicleIdentificationRequestVin(vehicle_identification_request_vin) => {
+                VehicleIdentificationRequestVinCodec {}
+                    .encoded_len(vehicle_identification_request_vin)
+            }
+            DoipPayload::VehicleAnnouncementMessage(vehicle_announcement_message) => {
+                VehicleAnnouncementMessageCodec {}.encoded_len(vehicle_announcement_message)
+            }
+            DoipPayload::RoutingActivationRequest(routing_activation_request) => {
+                RoutingActivationRequestCodec {}.encoded_len(routing_activation_request)
+            }
+            DoipPayload::RoutingActivationResponse(routing_activation_response) => {
+                RoutingActivationResponseCodec {}.encoded_len(routing_activation_response)
+            }
+            DoipPayload::AliveCheckRequest(alive_check_request) => {
+                AliveCheckRequestCodec {}.encoded_len(alive_check_request)
+            }
+            DoipPayload::AliveCheckResponse(alive_check_response) => {
+                AliveCheckResponseCodec {}.encoded_len(alive_check_response)
+            }
+            DoipPayload::EntityStatusRequest(entity_status_request) => {
+                EntityStatusRequestCodec {}.encoded_len(entity_status_request)
+            }
+            DoipPayload::EntityStatusResponse(entity_status_response) => {
+                EntityStatusResponseCodec {}.encoded_len(entity_status_response)
+            }
+            DoipPayload::PowerInformationRequest(power_information_request) => {
+                PowerInformationRequestCodec {}.encoded_len(power_information_request)
+            }
+            DoipPayload::PowerInformationResponse(power_information_response) => {
+                PowerInformationResponseCodec {}.encoded_len(power_information_response)
+            }
+            DoipPayload::DiagnosticMessage(diagnostic_message) => {
+                DiagnosticMessageCodec {}.encoded_len(diagnostic_message)
+            }
+            DoipPayload::DiagnosticMessageAck(diagnostic_message_ack) => {
+                DiagnosticMessageAckCodec {}.encoded_len(diagnostic_message_ack)
+            }
+            DoipPayload::DiagnosticMessageNack(diagnostic_message_nack) => {
+                DiagnosticMessageNackCodec {}.encoded_len(diagnostic_message_nack)
+            }
+        }
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: DoipPayload,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         match item {
             DoipPayload::GenericNack(generic_nack) => {
                 GenericNackCodec {}.to_bytes(generic_nack, dst)?;
@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{AliveCheckRequest, DoipPayload},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -11,7 +13,15 @@ pub struct AliveCheckRequestCodec;
 impl Encoder<AliveCheckRequest> for AliveCheckRequestCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: AliveCheckRequest, _dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, _item: &AliveCheckRequest) -> usize {
+        0
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: AliveCheckRequest,
+        _dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let AliveCheckRequest {} = item;
 
         Ok(())
@@ -42,7 +52,7 @@ mod tests {
 
     use crate::{
         doip_message::payload::alive_check_request::AliveCheckRequestCodec, Decoder, DoipCodec,
-        Encoder,
+        DoipFrame, Encoder,
     };
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
@@ -57,7 +67,7 @@ mod tests {
 
     #[test]
     fn test_encode_alive_check_request_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -68,7 +78,7 @@ mod tests {
 
     #[test]
     fn test_decode_alive_check_request_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -80,7 +90,7 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
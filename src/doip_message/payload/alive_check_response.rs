@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{AliveCheckResponse, DoipPayload},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -11,10 +13,18 @@ pub struct AliveCheckResponseCodec;
 impl Encoder<AliveCheckResponse> for AliveCheckResponseCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: AliveCheckResponse, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &AliveCheckResponse) -> usize {
+        item.source_address.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: AliveCheckResponse,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let AliveCheckResponse { source_address } = item;
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
         Ok(())
     }
@@ -49,7 +59,7 @@ mod tests {
         payload::{AliveCheckResponse, DoipPayload},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -65,7 +75,7 @@ mod tests {
 
     #[test]
     fn test_encode_alive_check_response_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -79,7 +89,7 @@ mod tests {
 
     #[test]
     fn test_decode_alive_check_response_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -91,18 +101,18 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_alive_check_response_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[0x02, 0xfd, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x00];
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
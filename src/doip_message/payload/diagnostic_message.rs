@@ -6,6 +6,8 @@ use doip_definitions::{
     payload::{DiagnosticMessage, DoipPayload},
 };
 
+use bytes::BufMut;
+
 use crate::{doip_message::header::HeaderCodec, DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -14,18 +16,26 @@ pub struct DiagnosticMessageCodec {}
 impl Encoder<DiagnosticMessage> for DiagnosticMessageCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: DiagnosticMessage, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &DiagnosticMessage) -> usize {
+        item.source_address.len() + item.target_address.len() + item.message.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: DiagnosticMessage,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let DiagnosticMessage {
             source_address,
             target_address,
             message,
         } = item;
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
-        dst.extend_from_slice(&target_address);
+        dst.put_slice(&target_address);
 
-        dst.extend_from_slice(&message);
+        dst.put_slice(&message);
 
         Ok(())
     }
@@ -56,12 +66,13 @@ impl Decoder for DiagnosticMessageCodec {
             .try_into()
             .expect("If failed, source has been manupulated at runtime.");
 
-        let data_size = header.payload_length as usize - BASE_MSG_LEN;
-        let message = if src.len() >= DOIP_DIAG_MESSAGE_DATA_OFFSET + data_size {
-            src[DOIP_DIAG_MESSAGE_DATA_OFFSET..DOIP_DIAG_MESSAGE_DATA_OFFSET + data_size].to_vec()
-        } else {
-            vec![]
-        };
+        let data_size = (header.payload_length as usize)
+            .checked_sub(BASE_MSG_LEN)
+            .ok_or(DecodeError::TooShort)?;
+        let message = src
+            .get(DOIP_DIAG_MESSAGE_DATA_OFFSET..DOIP_DIAG_MESSAGE_DATA_OFFSET + data_size)
+            .ok_or(DecodeError::TooShort)?
+            .to_vec();
 
         let item = DiagnosticMessage {
             source_address,
@@ -6,6 +6,8 @@ use doip_definitions::{
     payload::{DiagnosticAckCode, DiagnosticMessageAck, DoipPayload},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -14,10 +16,14 @@ pub struct DiagnosticMessageAckCodec;
 impl Encoder<DiagnosticMessageAck> for DiagnosticMessageAckCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &DiagnosticMessageAck) -> usize {
+        item.source_address.len() + item.target_address.len() + item.ack_code.to_bytes().len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: DiagnosticMessageAck,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let DiagnosticMessageAck {
             source_address,
@@ -25,12 +31,12 @@ impl Encoder<DiagnosticMessageAck> for DiagnosticMessageAckCodec {
             ack_code,
         } = item;
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
-        dst.extend_from_slice(&target_address);
+        dst.put_slice(&target_address);
 
         let ack_code_bytes = ack_code.to_bytes();
-        dst.extend_from_slice(ack_code_bytes);
+        dst.put_slice(ack_code_bytes);
 
         Ok(())
     }
@@ -104,7 +110,7 @@ mod tests {
         payload::{DiagnosticAckCode, DiagnosticMessageAck, DoipPayload},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder, FromBytes, ToBytes};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -144,7 +150,7 @@ mod tests {
 
     #[test]
     fn test_encode_diagnostic_message_ack_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -158,7 +164,7 @@ mod tests {
 
     #[test]
     fn test_decode_diagnostic_message_ack_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -170,12 +176,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_diagnostic_message_ack_invalid_diagnostic_ack_code() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -189,7 +195,7 @@ mod tests {
 
     #[test]
     fn test_decode_diagnostic_message_ack_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -198,6 +204,6 @@ mod tests {
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
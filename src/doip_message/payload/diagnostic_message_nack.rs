@@ -6,6 +6,8 @@ use doip_definitions::{
     payload::{DiagnosticMessageNack, DiagnosticNackCode, DoipPayload},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -14,10 +16,14 @@ pub struct DiagnosticMessageNackCodec;
 impl Encoder<DiagnosticMessageNack> for DiagnosticMessageNackCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &DiagnosticMessageNack) -> usize {
+        item.source_address.len() + item.target_address.len() + item.nack_code.to_bytes().len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: DiagnosticMessageNack,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let DiagnosticMessageNack {
             source_address,
@@ -25,12 +31,12 @@ impl Encoder<DiagnosticMessageNack> for DiagnosticMessageNackCodec {
             nack_code,
         } = item;
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
-        dst.extend_from_slice(&target_address);
+        dst.put_slice(&target_address);
 
         let nack_code_bytes = nack_code.to_bytes();
-        dst.extend_from_slice(nack_code_bytes);
+        dst.put_slice(nack_code_bytes);
 
         Ok(())
     }
@@ -149,7 +155,7 @@ mod tests {
         payload::{DiagnosticMessageNack, DiagnosticNackCode, DoipPayload},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder, FromBytes, ToBytes};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -240,7 +246,7 @@ mod tests {
 
     #[test]
     fn test_encode_diagnostic_message_ack_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -254,7 +260,7 @@ mod tests {
 
     #[test]
     fn test_decode_diagnostic_message_nack_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -266,12 +272,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_diagnostic_message_nack_invalid_diagnostic_nack_code() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -285,7 +291,7 @@ mod tests {
 
     #[test]
     fn test_decode_diagnostic_message_nack_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -294,6 +300,6 @@ mod tests {
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
@@ -1,40 +1,53 @@
 use doip_definitions::{
     definitions::{
-        DOIP_ENTITY_STATUS_RESPONSE_LEN, DOIP_ENTITY_STATUS_RESPONSE_MCTS_OFFSET,
-        DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN, DOIP_ENTITY_STATUS_RESPONSE_MDS_OFFSET,
+        DOIP_ENTITY_STATUS_RESPONSE_MCTS_OFFSET, DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN,
         DOIP_ENTITY_STATUS_RESPONSE_NCTS_OFFSET, DOIP_HEADER_LEN,
     },
     payload::{DoipPayload, EntityStatusResponse, NodeType},
 };
 
-use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
+use bytes::BufMut;
 
+use crate::{
+    DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes,
+    DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT,
+};
+
+/// Encodes and decodes the mandatory fields of an entity status response.
+///
+/// The optional max data size field is carried by
+/// `PayloadExtension::MaxDataSize`, the field of the same name in the payload
+/// is neither encoded nor filled in when decoding.
 #[derive(Debug)]
 pub struct EntityStatusResponseCodec;
 
 impl Encoder<EntityStatusResponse> for EntityStatusResponseCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &EntityStatusResponse) -> usize {
+        item.node_type.to_bytes().len()
+            + item.max_concurrent_sockets.len()
+            + item.currently_open_sockets.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: EntityStatusResponse,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let EntityStatusResponse {
             node_type,
             max_concurrent_sockets,
             currently_open_sockets,
-            max_data_size,
+            max_data_size: _,
         } = item;
 
         let node_type_bytes = node_type.to_bytes();
-        dst.extend_from_slice(node_type_bytes);
-
-        dst.extend_from_slice(&max_concurrent_sockets);
+        dst.put_slice(node_type_bytes);
 
-        dst.extend_from_slice(&currently_open_sockets);
+        dst.put_slice(&max_concurrent_sockets);
 
-        dst.extend_from_slice(&max_data_size);
+        dst.put_slice(&currently_open_sockets);
 
         Ok(())
     }
@@ -55,7 +68,7 @@ impl Decoder for EntityStatusResponseCodec {
     type Error = DecodeError;
 
     fn decode_from_bytes(&mut self, src: &[u8]) -> Result<Option<Self::Item>, Self::Error> {
-        if src.len() < DOIP_HEADER_LEN + DOIP_ENTITY_STATUS_RESPONSE_LEN {
+        if src.len() < DOIP_HEADER_LEN + DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT {
             return Err(DecodeError::TooShort);
         }
 
@@ -73,16 +86,11 @@ impl Decoder for EntityStatusResponseCodec {
             .try_into()
             .expect("If failed, source has been manupulated at runtime.");
 
-        let max_data_size = src[DOIP_ENTITY_STATUS_RESPONSE_MDS_OFFSET
-            ..DOIP_ENTITY_STATUS_RESPONSE_MDS_OFFSET + DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN]
-            .try_into()
-            .expect("If failed, source has been manupulated at runtime.");
-
         let item = EntityStatusResponse {
             node_type,
             max_concurrent_sockets,
             currently_open_sockets,
-            max_data_size,
+            max_data_size: [0; DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN],
         };
 
         Ok(Some(DoipPayload::EntityStatusResponse(item)))
@@ -112,19 +120,40 @@ mod tests {
         payload::{DoipPayload, EntityStatusResponse, NodeType},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{
+        Decoder, DoipCodec, DoipFrame, Encoder, ExtendedMessage, FromBytes, PayloadExtension,
+        ToBytes,
+    };
+
+    static SUCCESS_ROOT: ExtendedMessage = ExtendedMessage {
+        message: DoipMessage {
+            header: DoipHeader {
+                protocol_version: ProtocolVersion::Iso13400_2012,
+                inverse_protocol_version: 0xfd,
+                payload_type: PayloadType::EntityStatusResponse,
+                payload_length: 7u32,
+            },
+            payload: DoipPayload::EntityStatusResponse(EntityStatusResponse {
+                node_type: NodeType::DoipGateway,
+                max_concurrent_sockets: [0x00],
+                currently_open_sockets: [0x00],
+                max_data_size: [0x00, 0x00, 0x00, 0x00],
+            }),
+        },
+        extension: PayloadExtension::MaxDataSize([0x00, 0x00, 0x00, 0x00]),
+    };
 
-    static SUCCESS_ROOT: DoipMessage = DoipMessage {
+    static SUCCESS_ROOT_SHORT: DoipMessage = DoipMessage {
         header: DoipHeader {
             protocol_version: ProtocolVersion::Iso13400_2012,
             inverse_protocol_version: 0xfd,
             payload_type: PayloadType::EntityStatusResponse,
-            payload_length: 7u32,
+            payload_length: 3u32,
         },
         payload: DoipPayload::EntityStatusResponse(EntityStatusResponse {
-            node_type: NodeType::DoipGateway,
-            max_concurrent_sockets: [0x00],
-            currently_open_sockets: [0x00],
+            node_type: NodeType::DoipNode,
+            max_concurrent_sockets: [0x04],
+            currently_open_sockets: [0x01],
             max_data_size: [0x00, 0x00, 0x00, 0x00],
         }),
     };
@@ -160,7 +189,7 @@ mod tests {
 
     #[test]
     fn test_encode_entity_status_response_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -177,7 +206,7 @@ mod tests {
 
     #[test]
     fn test_decode_entity_status_response_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -189,12 +218,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Extended(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_entity_status_response_invalid_node_type() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -209,7 +238,7 @@ mod tests {
 
     #[test]
     fn test_decode_entity_status_response_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -218,6 +247,85 @@ mod tests {
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
+    }
+
+    #[test]
+    fn test_encode_entity_status_response_short() {
+        let mut encoder = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        assert_eq!(encoder.encoded_len(&SUCCESS_ROOT_SHORT), 11);
+
+        let bytes = encoder.to_bytes(SUCCESS_ROOT_SHORT.clone(), &mut dst);
+
+        assert!(bytes.is_ok(), "Expected bytes to be ok.");
+        assert_eq!(
+            *dst,
+            [0x02, 0xfd, 0x40, 0x02, 0x00, 0x00, 0x00, 0x03, 0x01, 0x04, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_encode_entity_status_response_ignores_payload_max_data_size() {
+        let mut encoder = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let mut msg = SUCCESS_ROOT_SHORT.clone();
+        if let DoipPayload::EntityStatusResponse(payload) = &mut msg.payload {
+            payload.max_data_size = [0x00, 0x00, 0x0f, 0xff];
+        }
+
+        assert_eq!(encoder.encoded_len(&msg), 11);
+
+        let bytes = encoder.to_bytes(msg, &mut dst);
+
+        assert!(bytes.is_ok());
+        assert_eq!(dst.len(), 11);
+    }
+
+    #[test]
+    fn test_decode_entity_status_response_short() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        let _ = codec.to_bytes(SUCCESS_ROOT_SHORT.clone(), &mut dst);
+        let msg = codec.decode_from_bytes(&dst);
+
+        assert_eq!(
+            msg.unwrap(),
+            Some(DoipFrame::Message(SUCCESS_ROOT_SHORT.clone()))
+        );
+    }
+
+    #[test]
+    fn test_build_entity_status_response() {
+        let short = DoipCodec::build_entity_status_response(
+            ProtocolVersion::Iso13400_2012,
+            NodeType::DoipNode,
+            4,
+            1,
+            None,
+        );
+        assert_eq!(
+            short.unwrap(),
+            DoipFrame::Message(SUCCESS_ROOT_SHORT.clone())
+        );
+
+        let long = DoipCodec::build_entity_status_response(
+            ProtocolVersion::Iso13400_2012,
+            NodeType::DoipGateway,
+            0,
+            0,
+            Some(0x0000_0fff),
+        )
+        .unwrap();
+        let mut expected = SUCCESS_ROOT.clone();
+        expected.extension = PayloadExtension::MaxDataSize([0x00, 0x00, 0x0f, 0xff]);
+        assert_eq!(long, DoipFrame::Extended(expected));
+
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let _ = codec.to_bytes(long, &mut dst);
+        assert_eq!(&dst[11..], &[0x00, 0x00, 0x0f, 0xff]);
     }
 }
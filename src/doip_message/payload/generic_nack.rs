@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{DoipPayload, GenericNack, NackCode},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -11,12 +13,20 @@ pub struct GenericNackCodec;
 impl Encoder<GenericNack> for GenericNackCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: GenericNack, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &GenericNack) -> usize {
+        item.nack_code.to_bytes().len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: GenericNack,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let GenericNack { nack_code } = item;
 
         let bytes = nack_code.to_bytes();
 
-        dst.extend_from_slice(bytes);
+        dst.put_slice(bytes);
 
         Ok(())
     }
@@ -82,8 +92,8 @@ impl FromBytes for NackCode {
 #[cfg(test)]
 mod tests {
     use crate::{
-        doip_message::payload::generic_nack::GenericNackCodec, Decoder, DoipCodec, Encoder,
-        FromBytes, ToBytes,
+        doip_message::payload::generic_nack::GenericNackCodec, Decoder, DoipCodec, DoipFrame,
+        Encoder, FromBytes, ToBytes,
     };
     use doip_definitions::{
         header::{DoipHeader, PayloadType, ProtocolVersion},
@@ -105,7 +115,7 @@ mod tests {
 
     #[test]
     fn test_decode_generic_nack_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -117,7 +127,7 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
@@ -134,7 +144,7 @@ mod tests {
 
     #[test]
     fn test_decode_generic_nack_invalid_nack() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[0x02, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xff];
@@ -146,7 +156,7 @@ mod tests {
 
     #[test]
     fn test_encode_single_message_generic_nack_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{DoipPayload, PowerInformationRequest},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -11,10 +13,14 @@ pub struct PowerInformationRequestCodec;
 impl Encoder<PowerInformationRequest> for PowerInformationRequestCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, _item: &PowerInformationRequest) -> usize {
+        0
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: PowerInformationRequest,
-        _dst: &mut Vec<u8>,
+        _dst: &mut B,
     ) -> Result<(), Self::Error> {
         let PowerInformationRequest {} = item;
 
@@ -48,7 +54,7 @@ mod tests {
 
     use crate::{
         doip_message::payload::power_information_request::PowerInformationRequestCodec, Decoder,
-        DoipCodec, Encoder,
+        DoipCodec, DoipFrame, Encoder,
     };
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
@@ -63,7 +69,7 @@ mod tests {
 
     #[test]
     fn test_encode_power_information_request_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -74,7 +80,7 @@ mod tests {
 
     #[test]
     fn test_decode_power_information_request_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -86,7 +92,7 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{DoipPayload, PowerInformationResponse, PowerMode},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -11,15 +13,19 @@ pub struct PowerInformationResponseCodec;
 impl Encoder<PowerInformationResponse> for PowerInformationResponseCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &PowerInformationResponse) -> usize {
+        item.power_mode.to_bytes().len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: PowerInformationResponse,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let PowerInformationResponse { power_mode } = item;
 
         let power_mode_bytes = power_mode.to_bytes();
-        dst.extend_from_slice(power_mode_bytes);
+        dst.put_slice(power_mode_bytes);
 
         Ok(())
     }
@@ -79,7 +85,7 @@ mod tests {
         payload::{DoipPayload, PowerInformationResponse, PowerMode},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder, FromBytes, ToBytes};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -131,7 +137,7 @@ mod tests {
 
     #[test]
     fn test_encode_power_information_response_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -142,7 +148,7 @@ mod tests {
 
     #[test]
     fn test_decode_power_information_response_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -154,12 +160,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_power_information_response_invalid_power_mode() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01, 0x42];
@@ -171,13 +177,13 @@ mod tests {
 
     #[test]
     fn test_decode_power_information_response_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[0x02, 0xfd, 0x40, 0x04, 0x00, 0x00, 0x00, 0x01];
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
@@ -7,6 +7,8 @@ use doip_definitions::{
     payload::{ActivationType, DoipPayload, RoutingActivationRequest},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -15,19 +17,27 @@ pub struct RoutingActivationRequestCodec;
 impl Encoder<RoutingActivationRequest> for RoutingActivationRequestCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: RoutingActivationRequest, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &RoutingActivationRequest) -> usize {
+        item.source_address.len() + item.activation_type.to_bytes().len() + item.buffer.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: RoutingActivationRequest,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         let RoutingActivationRequest {
             source_address,
             activation_type,
             buffer,
         } = item;
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
         let activation_type_bytes = activation_type.to_bytes();
-        dst.extend_from_slice(activation_type_bytes);
+        dst.put_slice(activation_type_bytes);
 
-        dst.extend_from_slice(&buffer);
+        dst.put_slice(&buffer);
 
         Ok(())
     }
@@ -105,7 +115,7 @@ mod tests {
         payload::{ActivationType, DoipPayload, RoutingActivationRequest},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder, FromBytes, ToBytes};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -158,7 +168,7 @@ mod tests {
 
     #[test]
     fn test_decode_routing_activation_request_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -170,12 +180,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_encode_routing_activation_request_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -192,7 +202,7 @@ mod tests {
 
     #[test]
     fn test_decode_routing_activation_request_invalid_activation_type() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -207,7 +217,7 @@ mod tests {
 
     #[test]
     fn test_decode_routing_activation_request_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -216,6 +226,6 @@ mod tests {
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
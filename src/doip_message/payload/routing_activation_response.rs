@@ -8,6 +8,8 @@ use doip_definitions::{
     payload::{ActivationCode, DoipPayload, RoutingActivationResponse},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder, FromBytes, ToBytes};
 
 #[derive(Debug)]
@@ -16,10 +18,17 @@ pub struct RoutingActivationResponseCodec;
 impl Encoder<RoutingActivationResponse> for RoutingActivationResponseCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &RoutingActivationResponse) -> usize {
+        item.logical_address.len()
+            + item.source_address.len()
+            + item.activation_code.to_bytes().len()
+            + item.buffer.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: RoutingActivationResponse,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let RoutingActivationResponse {
             logical_address,
@@ -28,14 +37,14 @@ impl Encoder<RoutingActivationResponse> for RoutingActivationResponseCodec {
             buffer,
         } = item;
 
-        dst.extend_from_slice(&logical_address);
+        dst.put_slice(&logical_address);
 
-        dst.extend_from_slice(&source_address);
+        dst.put_slice(&source_address);
 
         let activation_code_bytes = activation_code.to_bytes();
-        dst.extend_from_slice(activation_code_bytes);
+        dst.put_slice(activation_code_bytes);
 
-        dst.extend_from_slice(&buffer);
+        dst.put_slice(&buffer);
 
         Ok(())
     }
@@ -198,7 +207,7 @@ mod tests {
         payload::{ActivationCode, DoipPayload, RoutingActivationResponse},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder, FromBytes, ToBytes};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder, FromBytes, ToBytes};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -355,7 +364,7 @@ mod tests {
 
     #[test]
     fn test_encode_routing_activation_response_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -372,7 +381,7 @@ mod tests {
 
     #[test]
     fn test_decode_routing_activation_response_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -384,12 +393,12 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_routing_activation_response_invalid_activation_type() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -404,7 +413,7 @@ mod tests {
 
     #[test]
     fn test_decode_routing_activation_response_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[
@@ -413,6 +422,6 @@ mod tests {
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{DoipPayload, VehicleIdentificationRequest},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -11,10 +13,14 @@ pub struct VehicleIdentificationRequestCodec;
 impl Encoder<VehicleIdentificationRequest> for VehicleIdentificationRequestCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, _item: &VehicleIdentificationRequest) -> usize {
+        0
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: VehicleIdentificationRequest,
-        _dst: &mut Vec<u8>,
+        _dst: &mut B,
     ) -> Result<(), Self::Error> {
         let VehicleIdentificationRequest {} = item;
 
@@ -48,7 +54,7 @@ mod tests {
 
     use crate::{
         doip_message::payload::vehicle_identification_request::VehicleIdentificationRequestCodec,
-        Decoder, DoipCodec, Encoder,
+        Decoder, DoipCodec, DoipFrame, Encoder,
     };
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
@@ -63,7 +69,7 @@ mod tests {
 
     #[test]
     fn test_encode_vehicle_identification_request_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -74,7 +80,7 @@ mod tests {
 
     #[test]
     fn test_decode_vehicle_identification_request_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -85,7 +91,7 @@ mod tests {
 
         assert!(opt.is_some());
         let res = opt.unwrap();
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
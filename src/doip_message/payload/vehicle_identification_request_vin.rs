@@ -3,6 +3,8 @@ use doip_definitions::{
     payload::{DoipPayload, VehicleIdentificationRequestVin},
 };
 
+use bytes::BufMut;
+
 use crate::{DecodeError, Decoder, EncodeError, Encoder};
 
 #[derive(Debug)]
@@ -11,14 +13,18 @@ pub struct VehicleIdentificationRequestVinCodec;
 impl Encoder<VehicleIdentificationRequestVin> for VehicleIdentificationRequestVinCodec {
     type Error = EncodeError;
 
-    fn to_bytes(
+    fn encoded_len(&self, item: &VehicleIdentificationRequestVin) -> usize {
+        item.vin.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
         &mut self,
         item: VehicleIdentificationRequestVin,
-        dst: &mut Vec<u8>,
+        dst: &mut B,
     ) -> Result<(), Self::Error> {
         let VehicleIdentificationRequestVin { vin } = item;
 
-        dst.extend_from_slice(&vin);
+        dst.put_slice(&vin);
 
         Ok(())
     }
@@ -52,7 +58,7 @@ mod tests {
         payload::{DoipPayload, VehicleIdentificationRequestVin},
     };
 
-    use crate::{Decoder, DoipCodec, Encoder};
+    use crate::{Decoder, DoipCodec, DoipFrame, Encoder};
 
     static SUCCESS_ROOT: DoipMessage = DoipMessage {
         header: DoipHeader {
@@ -68,7 +74,7 @@ mod tests {
 
     #[test]
     fn test_encode_vehicle_identification_request_vin_success() {
-        let mut encoder = DoipCodec {};
+        let mut encoder = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = encoder.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -85,7 +91,7 @@ mod tests {
 
     #[test]
     fn test_decode_vehicle_identification_request_vin_success() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let _ = codec.to_bytes(SUCCESS_ROOT.clone(), &mut dst);
@@ -97,18 +103,18 @@ mod tests {
         assert!(opt.is_some());
         let res = opt.unwrap();
 
-        assert_eq!(res, SUCCESS_ROOT);
+        assert_eq!(res, DoipFrame::Message(SUCCESS_ROOT.clone()));
     }
 
     #[test]
     fn test_decode_vehicle_identification_request_vin_too_short() {
-        let mut codec = DoipCodec {};
+        let mut codec = DoipCodec::new();
         let mut dst = Vec::<u8>::new();
 
         let bytes = &[0x02, 0xfd, 0x00, 0x03, 0x00, 0x00, 0x00, 0x06, 0xff];
         dst.extend_from_slice(bytes);
         let msg = codec.decode_from_bytes(&mut dst);
 
-        assert!(msg.is_err());
+        assert!(matches!(msg, Ok(None)));
     }
 }
@@ -1,87 +1,349 @@
-use doip_definitions::{header::PayloadType, message::DoipMessage, payload::DoipPayload};
+use bytes::BufMut;
+use doip_definitions::{
+    definitions::DOIP_HEADER_LEN,
+    header::{DoipHeader, PayloadType, ProtocolVersion},
+    message::DoipMessage,
+    payload::{DoipPayload, EntityStatusResponse, NodeType},
+};
 
 use crate::{
     doip_message::{header::HeaderCodec, payload::PayloadCodec},
     error::EncodeError,
-    DoipCodec, Encoder,
+    CustomMessage, DoipCodec, DoipFrame, Encoder, ExtendedMessage, FixedMessage, PayloadExtension,
+    RawMessage, ToBytes,
 };
 
 impl Encoder<DoipMessage> for DoipCodec {
     type Error = EncodeError;
 
-    fn to_bytes(&mut self, item: DoipMessage, dst: &mut Vec<u8>) -> Result<(), Self::Error> {
+    fn encoded_len(&self, item: &DoipMessage) -> usize {
+        HeaderCodec {}.encoded_len(&item.header) + PayloadCodec {}.encoded_len(&item.payload)
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: DoipMessage,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
         validate_payload_match(&item)?;
 
         let header_len = item.header.payload_length as usize;
-        let () = HeaderCodec {}.to_bytes(item.header, dst)?;
+        validate_payload_length(header_len, PayloadCodec {}.encoded_len(&item.payload))?;
+
+        let violations = self.check(&item);
 
-        let before_len = dst.len();
+        let () = HeaderCodec {}.to_bytes(item.header, dst)?;
         let () = PayloadCodec {}.to_bytes(item.payload, dst)?;
-        let after_len = dst.len();
 
-        validate_payload_length(header_len, after_len - before_len)?;
+        self.record_violations(violations);
 
         Ok(())
     }
 }
 
-fn validate_payload_match(item: &DoipMessage) -> Result<(), EncodeError> {
-    let valid = match item.payload {
-        DoipPayload::GenericNack(_) => item.header.payload_type == PayloadType::GenericNack,
-        DoipPayload::VehicleIdentificationRequest(_) => {
-            item.header.payload_type == PayloadType::VehicleIdentificationRequest
-        }
-        DoipPayload::VehicleIdentificationRequestEid(_) => {
-            item.header.payload_type == PayloadType::VehicleIdentificationRequestEid
-        }
-        DoipPayload::VehicleIdentificationRequestVin(_) => {
-            item.header.payload_type == PayloadType::VehicleIdentificationRequestVin
-        }
-        DoipPayload::VehicleAnnouncementMessage(_) => {
-            item.header.payload_type == PayloadType::VehicleAnnouncementMessage
-        }
-        DoipPayload::RoutingActivationRequest(_) => {
-            item.header.payload_type == PayloadType::RoutingActivationRequest
-        }
-        DoipPayload::RoutingActivationResponse(_) => {
-            item.header.payload_type == PayloadType::RoutingActivationResponse
-        }
-        DoipPayload::AliveCheckRequest(_) => {
-            item.header.payload_type == PayloadType::AliveCheckRequest
-        }
-        DoipPayload::AliveCheckResponse(_) => {
-            item.header.payload_type == PayloadType::AliveCheckResponse
-        }
-        DoipPayload::EntityStatusRequest(_) => {
-            item.header.payload_type == PayloadType::EntityStatusRequest
-        }
-        DoipPayload::EntityStatusResponse(_) => {
-            item.header.payload_type == PayloadType::EntityStatusResponse
-        }
-        DoipPayload::PowerInformationRequest(_) => {
-            item.header.payload_type == PayloadType::PowerInformationRequest
+impl Encoder<RawMessage> for DoipCodec {
+    type Error = EncodeError;
+
+    fn encoded_len(&self, item: &RawMessage) -> usize {
+        DOIP_HEADER_LEN + item.payload.len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: RawMessage,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
+        let RawMessage {
+            protocol_version,
+            payload_type,
+            payload,
+        } = item;
+
+        let payload_length =
+            u32::try_from(payload.len()).map_err(|_| EncodeError::PayloadLengthValidation)?;
+        let protocol_version_byte = protocol_version.to_bytes()[0];
+
+        dst.put_u8(protocol_version_byte);
+        dst.put_u8(!protocol_version_byte);
+        dst.put_u16(payload_type);
+        dst.put_u32(payload_length);
+        dst.put_slice(&payload);
+
+        Ok(())
+    }
+}
+
+impl Encoder<CustomMessage> for DoipCodec {
+    type Error = EncodeError;
+
+    fn encoded_len(&self, item: &CustomMessage) -> usize {
+        DOIP_HEADER_LEN
+            + self
+                .custom_payloads
+                .encoded_len(item.payload_type, &item.payload)
+                .unwrap_or_default()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: CustomMessage,
+        mut dst: &mut B,
+    ) -> Result<(), Self::Error> {
+        let CustomMessage {
+            protocol_version,
+            payload_type,
+            payload,
+        } = item;
+
+        let payload_length = self
+            .custom_payloads
+            .encoded_len(payload_type, &payload)
+            .ok_or(EncodeError::PayloadTypeValidation)?;
+
+        if dst.remaining_mut() < DOIP_HEADER_LEN + payload_length {
+            return Err(EncodeError::BufferTooSmall);
         }
-        DoipPayload::PowerInformationResponse(_) => {
-            item.header.payload_type == PayloadType::PowerInformationResponse
+
+        let protocol_version_byte = protocol_version.to_bytes()[0];
+
+        dst.put_u8(protocol_version_byte);
+        dst.put_u8(!protocol_version_byte);
+        dst.put_u16(payload_type);
+        dst.put_u32(
+            u32::try_from(payload_length).map_err(|_| EncodeError::PayloadLengthValidation)?,
+        );
+
+        // The header has already been written, make sure the codec kept the
+        // promise of its `encoded_len`.
+        let remaining = dst.remaining_mut();
+        self.custom_payloads
+            .encode(payload_type, &payload, &mut dst)?;
+        validate_payload_length(payload_length, remaining - dst.remaining_mut())?;
+
+        Ok(())
+    }
+}
+
+impl Encoder<ExtendedMessage> for DoipCodec {
+    type Error = EncodeError;
+
+    fn encoded_len(&self, item: &ExtendedMessage) -> usize {
+        self.encoded_len(&item.message) + item.extension.encoded_len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: ExtendedMessage,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
+        let ExtendedMessage { message, extension } = item;
+
+        validate_payload_match(&message)?;
+
+        if !extension.is_valid_for(&message.payload) {
+            return Err(EncodeError::PayloadTypeValidation);
         }
-        DoipPayload::DiagnosticMessage(_) => {
-            item.header.payload_type == PayloadType::DiagnosticMessage
+
+        let header_len = message.header.payload_length as usize;
+        validate_payload_length(
+            header_len,
+            PayloadCodec {}.encoded_len(&message.payload) + extension.encoded_len(),
+        )?;
+
+        let violations = self.check(&message);
+
+        let () = HeaderCodec {}.to_bytes(message.header, dst)?;
+        let () = PayloadCodec {}.to_bytes(message.payload, dst)?;
+        dst.put_slice(extension.as_bytes());
+
+        self.record_violations(violations);
+
+        Ok(())
+    }
+}
+
+impl Encoder<DoipFrame> for DoipCodec {
+    type Error = EncodeError;
+
+    fn encoded_len(&self, item: &DoipFrame) -> usize {
+        match item {
+            DoipFrame::Message(message) => self.encoded_len(message),
+            DoipFrame::Raw(raw) => self.encoded_len(raw),
+            DoipFrame::Custom(custom) => self.encoded_len(custom),
+            DoipFrame::Extended(extended) => self.encoded_len(extended),
         }
-        DoipPayload::DiagnosticMessageAck(_) => {
-            item.header.payload_type == PayloadType::DiagnosticMessageAck
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: DoipFrame,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
+        match item {
+            DoipFrame::Message(message) => self.to_bytes(message, dst),
+            DoipFrame::Raw(raw) => self.to_bytes(raw, dst),
+            DoipFrame::Custom(custom) => self.to_bytes(custom, dst),
+            DoipFrame::Extended(extended) => self.to_bytes(extended, dst),
         }
-        DoipPayload::DiagnosticMessageNack(_) => {
-            item.header.payload_type == PayloadType::DiagnosticMessageNack
+    }
+}
+
+impl DoipCodec {
+    /// Builds a message for the payload provided, deriving the inverse protocol
+    /// version, payload type and payload length of the header from it.
+    ///
+    /// Messages built this way always pass the header validation of the
+    /// encoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncodeError::PayloadLengthValidation` if the encoded payload
+    /// exceeds the maximum length a header can announce.
+    pub fn build_message(
+        protocol_version: ProtocolVersion,
+        payload: DoipPayload,
+    ) -> Result<DoipMessage, EncodeError> {
+        let payload_length = u32::try_from(PayloadCodec {}.encoded_len(&payload))
+            .map_err(|_| EncodeError::PayloadLengthValidation)?;
+
+        Ok(DoipMessage {
+            header: DoipHeader {
+                protocol_version,
+                inverse_protocol_version: !protocol_version.to_bytes()[0],
+                payload_type: payload_type_of(&payload),
+                payload_length,
+            },
+            payload,
+        })
+    }
+
+    /// Builds an entity status response, carrying the optional max data size
+    /// field as a `PayloadExtension::MaxDataSize` if `max_data_size` is `Some`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncodeError::PayloadLengthValidation` if the payload length
+    /// cannot be announced by a header.
+    pub fn build_entity_status_response(
+        protocol_version: ProtocolVersion,
+        node_type: NodeType,
+        max_concurrent_sockets: u8,
+        currently_open_sockets: u8,
+        max_data_size: Option<u32>,
+    ) -> Result<DoipFrame, EncodeError> {
+        let payload = EntityStatusResponse {
+            node_type,
+            max_concurrent_sockets: [max_concurrent_sockets],
+            currently_open_sockets: [currently_open_sockets],
+            max_data_size: [0; 4],
+        };
+
+        let mut message =
+            Self::build_message(protocol_version, DoipPayload::EntityStatusResponse(payload))?;
+
+        let Some(max_data_size) = max_data_size else {
+            return Ok(DoipFrame::Message(message));
+        };
+
+        let extension = PayloadExtension::MaxDataSize(max_data_size.to_be_bytes());
+        message.header.payload_length += u32::try_from(extension.encoded_len())
+            .map_err(|_| EncodeError::PayloadLengthValidation)?;
+
+        Ok(DoipFrame::Extended(ExtendedMessage { message, extension }))
+    }
+
+    /// Encodes an item into a fixed size buffer without allocating.
+    ///
+    /// Returns the number of bytes written, the buffer is left untouched if it
+    /// is too small for the item. It may hold a partial frame if encoding
+    /// failed otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncodeError::BufferTooSmall` if the item does not fit into `dst`,
+    /// or any other `EncodeError` if the item could not be encoded.
+    pub fn encode_to_slice<Item>(
+        &mut self,
+        item: Item,
+        dst: &mut [u8],
+    ) -> Result<usize, EncodeError>
+    where
+        Self: Encoder<Item, Error = EncodeError>,
+    {
+        let len = self.encoded_len(&item);
+        if dst.len() < len {
+            return Err(EncodeError::BufferTooSmall);
         }
-    };
 
-    if valid {
+        // The whole buffer is handed on, so that a custom payload codec writing
+        // more than it announced is reported instead of running out of space.
+        let mut dst = dst;
+        self.to_bytes(item, &mut dst)?;
+
+        Ok(len)
+    }
+}
+
+impl<const N: usize> Encoder<FixedMessage<N>> for DoipCodec {
+    type Error = EncodeError;
+
+    fn encoded_len(&self, item: &FixedMessage<N>) -> usize {
+        DOIP_HEADER_LEN + item.payload().len()
+    }
+
+    fn to_bytes<B: BufMut + ?Sized>(
+        &mut self,
+        item: FixedMessage<N>,
+        dst: &mut B,
+    ) -> Result<(), Self::Error> {
+        let payload = item.payload();
+        let payload_length =
+            u32::try_from(payload.len()).map_err(|_| EncodeError::PayloadLengthValidation)?;
+        let protocol_version_byte = item.protocol_version().to_bytes()[0];
+
+        dst.put_u8(protocol_version_byte);
+        dst.put_u8(!protocol_version_byte);
+        dst.put_u16(item.payload_type_code());
+        dst.put_u32(payload_length);
+        dst.put_slice(payload);
+
+        Ok(())
+    }
+}
+
+fn validate_payload_match(item: &DoipMessage) -> Result<(), EncodeError> {
+    if item.header.payload_type == payload_type_of(&item.payload) {
         Ok(())
     } else {
         Err(EncodeError::PayloadTypeValidation)
     }
 }
 
+fn payload_type_of(payload: &DoipPayload) -> PayloadType {
+    match payload {
+        DoipPayload::GenericNack(_) => PayloadType::GenericNack,
+        DoipPayload::VehicleIdentificationRequest(_) => PayloadType::VehicleIdentificationRequest,
+        DoipPayload::VehicleIdentificationRequestEid(_) => {
+            PayloadType::VehicleIdentificationRequestEid
+        }
+        DoipPayload::VehicleIdentificationRequestVin(_) => {
+            PayloadType::VehicleIdentificationRequestVin
+        }
+        DoipPayload::VehicleAnnouncementMessage(_) => PayloadType::VehicleAnnouncementMessage,
+        DoipPayload::RoutingActivationRequest(_) => PayloadType::RoutingActivationRequest,
+        DoipPayload::RoutingActivationResponse(_) => PayloadType::RoutingActivationResponse,
+        DoipPayload::AliveCheckRequest(_) => PayloadType::AliveCheckRequest,
+        DoipPayload::AliveCheckResponse(_) => PayloadType::AliveCheckResponse,
+        DoipPayload::EntityStatusRequest(_) => PayloadType::EntityStatusRequest,
+        DoipPayload::EntityStatusResponse(_) => PayloadType::EntityStatusResponse,
+        DoipPayload::PowerInformationRequest(_) => PayloadType::PowerInformationRequest,
+        DoipPayload::PowerInformationResponse(_) => PayloadType::PowerInformationResponse,
+        DoipPayload::DiagnosticMessage(_) => PayloadType::DiagnosticMessage,
+        DoipPayload::DiagnosticMessageAck(_) => PayloadType::DiagnosticMessageAck,
+        DoipPayload::DiagnosticMessageNack(_) => PayloadType::DiagnosticMessageNack,
+    }
+}
+
 fn validate_payload_length(header_len: usize, length: usize) -> Result<(), EncodeError> {
     if header_len != length {
         return Err(EncodeError::PayloadLengthValidation);
@@ -89,18 +351,22 @@ fn validate_payload_length(header_len: usize, length: usize) -> Result<(), Encod
     Ok(())
 }
 
-impl tokio_util::codec::Encoder<DoipMessage> for DoipCodec {
+#[cfg(feature = "std")]
+impl<Item> tokio_util::codec::Encoder<Item> for DoipCodec
+where
+    DoipCodec: Encoder<Item, Error = EncodeError>,
+{
     type Error = EncodeError;
 
-    fn encode(
-        &mut self,
-        item: DoipMessage,
-        dst: &mut tokio_util::bytes::BytesMut,
-    ) -> Result<(), Self::Error> {
-        let mut heapless_dst = Vec::<u8>::new();
+    fn encode(&mut self, item: Item, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        dst.reserve(self.encoded_len(&item));
 
-        DoipCodec {}.to_bytes(item, &mut heapless_dst)?;
-        dst.extend_from_slice(&heapless_dst);
+        // Do not leave a partial frame behind for the transport to send
+        if let Err(err) = self.to_bytes(item, dst) {
+            dst.truncate(start);
+            return Err(err);
+        }
 
         Ok(())
     }
@@ -108,13 +374,17 @@ impl tokio_util::codec::Encoder<DoipMessage> for DoipCodec {
 
 #[cfg(test)]
 mod tests {
+    use bytes::BytesMut;
     use doip_definitions::{
         header::{DoipHeader, PayloadType, ProtocolVersion},
         message::DoipMessage,
-        payload::{AliveCheckRequest, DoipPayload, GenericNack, NackCode},
+        payload::{AliveCheckRequest, DiagnosticMessage, DoipPayload, GenericNack, NackCode},
     };
 
-    use crate::encoder::validate_payload_length;
+    use crate::{
+        encoder::validate_payload_length, test_util::with_length, DoipCodec, DoipFrame,
+        EncodeError, Encoder, RawMessage,
+    };
 
     use super::validate_payload_match;
 
@@ -156,4 +426,126 @@ mod tests {
         let invalid = validate_payload_length(1, 2);
         assert!(invalid.is_err());
     }
+
+    #[test]
+    fn test_encode_raw_message() {
+        let mut dst = Vec::<u8>::new();
+        let item = DoipFrame::Raw(RawMessage {
+            protocol_version: ProtocolVersion::Iso13400_2012,
+            payload_type: 0xf042,
+            payload: vec![0x01, 0x02, 0x03],
+        });
+
+        let res = DoipCodec::new().to_bytes(item, &mut dst);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            *dst,
+            [0x02, 0xfd, 0xf0, 0x42, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03]
+        );
+    }
+
+    fn diagnostic_message() -> DoipPayload {
+        DoipPayload::DiagnosticMessage(DiagnosticMessage {
+            source_address: [0x0e, 0x00],
+            target_address: [0x10, 0x01],
+            message: vec![0x22, 0xf1, 0x90],
+        })
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = with_length(diagnostic_message(), 7);
+
+        let len = codec.encoded_len(&item);
+        let res = codec.to_bytes(item, &mut dst);
+
+        assert!(res.is_ok());
+        assert_eq!(len, 15);
+        assert_eq!(dst.len(), len);
+    }
+
+    #[test]
+    fn test_encode_length_mismatch_writes_nothing() {
+        let mut dst = Vec::<u8>::new();
+
+        let res = DoipCodec::new().to_bytes(with_length(diagnostic_message(), 8), &mut dst);
+
+        assert!(matches!(res, Err(EncodeError::PayloadLengthValidation)));
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn test_encode_into_bytes_mut() {
+        let mut codec = DoipCodec::new();
+        let mut dst = BytesMut::new();
+
+        let res = tokio_util::codec::Encoder::encode(
+            &mut codec,
+            with_length(diagnostic_message(), 7),
+            &mut dst,
+        );
+
+        assert!(res.is_ok());
+        assert_eq!(
+            dst.as_ref(),
+            [
+                0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x00, 0x10, 0x01, 0x22, 0xf1,
+                0x90
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_into_slice() {
+        let mut buf = [0u8; 15];
+
+        let res =
+            DoipCodec::new().to_bytes(with_length(diagnostic_message(), 7), &mut &mut buf[..]);
+
+        assert!(res.is_ok());
+        assert_eq!(buf[..8], [0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07]);
+    }
+
+    #[test]
+    fn test_build_message() {
+        let payload = DoipPayload::DiagnosticMessage(DiagnosticMessage {
+            source_address: [0x0e, 0x00],
+            target_address: [0x10, 0x01],
+            message: vec![0x22, 0xf1, 0x90],
+        });
+
+        let msg = DoipCodec::build_message(ProtocolVersion::Iso13400_2019, payload).unwrap();
+
+        assert_eq!(
+            msg.header,
+            DoipHeader {
+                protocol_version: ProtocolVersion::Iso13400_2019,
+                inverse_protocol_version: 0xfc,
+                payload_type: PayloadType::DiagnosticMessage,
+                payload_length: 7,
+            }
+        );
+
+        let mut dst = Vec::<u8>::new();
+        let res = DoipCodec::new().to_bytes(msg, &mut dst);
+
+        assert!(res.is_ok());
+        assert_eq!(dst.len(), 15);
+    }
+
+    #[test]
+    fn test_build_message_empty_payload() {
+        let msg = DoipCodec::build_message(
+            ProtocolVersion::Iso13400_2012,
+            DoipPayload::AliveCheckRequest(AliveCheckRequest {}),
+        )
+        .unwrap();
+
+        assert_eq!(msg.header.payload_type, PayloadType::AliveCheckRequest);
+        assert_eq!(msg.header.payload_length, 0);
+        assert!(validate_payload_match(&msg).is_ok());
+    }
 }
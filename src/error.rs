@@ -1,4 +1,10 @@
-use std::io;
+use doip_definitions::{
+    header::ProtocolVersion,
+    message::DoipMessage,
+    payload::{DoipPayload, GenericNack, NackCode},
+};
+
+use crate::DoipCodec;
 
 /// A wrapper to encapsulate Parser and IO errors which can occur
 #[derive(thiserror::Error, Debug)]
@@ -35,59 +41,187 @@ pub enum DecodeError {
     #[error("invalid nack code")]
     InvalidNackCode,
 
-    /// invalid invalid protocol version
-    #[error("invalid invalid protocol version")]
+    /// invalid protocol version
+    #[error("invalid protocol version")]
     InvalidProtocolVersion,
 
-    /// invalid invalid payload type
-    #[error("invalid invalid payload type")]
+    /// invalid payload type
+    #[error("invalid payload type")]
     InvalidPayloadType,
 
-    /// invalid invalid action code
-    #[error("invalid invalid action code")]
+    /// invalid action code
+    #[error("invalid action code")]
     InvalidActionCode,
 
-    /// invalid invalid sync status
-    #[error("invalid invalid sync status")]
+    /// invalid sync status
+    #[error("invalid sync status")]
     InvalidSyncStatus,
 
-    /// invalid invalid activation type
-    #[error("invalid invalid activation type")]
+    /// invalid activation type
+    #[error("invalid activation type")]
     InvalidActivationType,
 
-    /// invalid invalid activation code
-    #[error("invalid invalid activation code")]
+    /// invalid activation code
+    #[error("invalid activation code")]
     InvalidActivationCode,
 
-    /// invalid invalid node type
-    #[error("invalid invalid node type")]
+    /// invalid node type
+    #[error("invalid node type")]
     InvalidNodeType,
 
-    /// invalid invalid power mode
-    #[error("invalid invalid power mode")]
+    /// invalid power mode
+    #[error("invalid power mode")]
     InvalidPowerMode,
 
-    /// invalid invalid diagnostic ack code
-    #[error("invalid invalid diagnostic ack code")]
+    /// invalid diagnostic ack code
+    #[error("invalid diagnostic ack code")]
     InvalidDiagnosticAckCode,
 
-    /// invalid invalid diagnostic nack code
-    #[error("invalid invalid diagnostic nack code")]
+    /// invalid diagnostic nack code
+    #[error("invalid diagnostic nack code")]
     InvalidDiagnosticNackCode,
 
+    /// protocol version not supported, or default protocol version used for a
+    /// payload type other than a vehicle identification request
+    #[error("protocol version not supported for this payload type")]
+    UnsupportedProtocolVersion,
+
+    /// announced payload length not allowed for the payload type
+    #[error("payload length not allowed for this payload type")]
+    InvalidPayloadLength,
+
     /// message received is too large for buffer
     #[error("message received is too large for buffer")]
     MessageTooLarge,
 
+    /// message received exceeds the currently available memory
+    #[error("message received exceeds the currently available memory")]
+    OutOfMemory,
+
     /// IO error from Stream
+    #[cfg(feature = "std")]
     #[error("Underlying I/O Error: {0}")]
-    IOError(#[from] io::Error),
+    IOError(#[from] std::io::Error),
 
     /// buffer is too small
     #[error("buffer is too small")]
     BufferTooSmall,
 }
 
+/// The reaction ISO 13400-2 expects from a `DoIP` entity after a message failed
+/// to decode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorResponse {
+    /// Reply with a generic NACK carrying the given code and discard the
+    /// message.
+    Nack(NackCode),
+
+    /// Reply with a generic NACK carrying the given code and close the socket.
+    NackAndClose(NackCode),
+
+    /// Close the socket without replying.
+    Close,
+
+    /// Discard the message without a generic NACK. The header was valid, so any
+    /// reply is specific to the payload type.
+    Discard,
+}
+
+impl ErrorResponse {
+    /// Returns the generic NACK code to reply with, if any.
+    #[must_use]
+    pub fn nack_code(self) -> Option<NackCode> {
+        match self {
+            ErrorResponse::Nack(nack_code) | ErrorResponse::NackAndClose(nack_code) => {
+                Some(nack_code)
+            }
+            ErrorResponse::Close | ErrorResponse::Discard => None,
+        }
+    }
+
+    /// Returns `true` if the socket has to be closed.
+    #[must_use]
+    pub fn closes_socket(self) -> bool {
+        matches!(self, ErrorResponse::NackAndClose(_) | ErrorResponse::Close)
+    }
+}
+
+impl DecodeError {
+    /// Maps the error onto the generic header handling of ISO 13400-2.
+    #[must_use]
+    pub fn response(&self) -> ErrorResponse {
+        match self {
+            DecodeError::InvalidHeader
+            | DecodeError::InvalidProtocolVersion
+            | DecodeError::FailedProtocolValidation
+            | DecodeError::UnsupportedProtocolVersion => {
+                ErrorResponse::NackAndClose(NackCode::IncorrectPatternFormat)
+            }
+            DecodeError::InvalidPayloadType => ErrorResponse::Nack(NackCode::UnknownPayloadType),
+            DecodeError::MessageTooLarge => ErrorResponse::Nack(NackCode::MessageTooLarge),
+            DecodeError::OutOfMemory => ErrorResponse::Nack(NackCode::OutOfMemory),
+            DecodeError::TooShort
+            | DecodeError::ExceededLength
+            | DecodeError::InvalidPayloadLength => {
+                ErrorResponse::NackAndClose(NackCode::InvalidPayloadLength)
+            }
+            #[cfg(feature = "std")]
+            DecodeError::IOError(_) => ErrorResponse::Close,
+            DecodeError::Unreachable
+            | DecodeError::TryFromBytes
+            | DecodeError::InvalidPayload
+            | DecodeError::InvalidNackCode
+            | DecodeError::InvalidActionCode
+            | DecodeError::InvalidSyncStatus
+            | DecodeError::InvalidActivationType
+            | DecodeError::InvalidActivationCode
+            | DecodeError::InvalidNodeType
+            | DecodeError::InvalidPowerMode
+            | DecodeError::InvalidDiagnosticAckCode
+            | DecodeError::InvalidDiagnosticNackCode
+            | DecodeError::BufferTooSmall => ErrorResponse::Discard,
+        }
+    }
+
+    /// Returns `true` if a field holds a value unknown to this crate, such as a
+    /// reserved or vendor-specific code.
+    pub(crate) fn is_unknown_value(&self) -> bool {
+        matches!(
+            self,
+            DecodeError::InvalidNackCode
+                | DecodeError::InvalidActionCode
+                | DecodeError::InvalidSyncStatus
+                | DecodeError::InvalidActivationType
+                | DecodeError::InvalidActivationCode
+                | DecodeError::InvalidNodeType
+                | DecodeError::InvalidPowerMode
+                | DecodeError::InvalidDiagnosticAckCode
+                | DecodeError::InvalidDiagnosticNackCode
+        )
+    }
+
+    /// Returns the generic NACK code to reply with, if any.
+    #[must_use]
+    pub fn nack_code(&self) -> Option<NackCode> {
+        self.response().nack_code()
+    }
+
+    /// Builds the generic NACK message to reply with, if any.
+    ///
+    /// The protocol version is the one used by the replying entity, as the
+    /// version of the offending message may be invalid.
+    #[must_use]
+    pub fn generic_nack(&self, protocol_version: ProtocolVersion) -> Option<DoipMessage> {
+        let nack_code = self.nack_code()?;
+
+        DoipCodec::build_message(
+            protocol_version,
+            DoipPayload::GenericNack(GenericNack { nack_code }),
+        )
+        .ok()
+    }
+}
+
 /// A wrapper to encapsulate IO errors which can occur
 #[derive(thiserror::Error, Debug)]
 pub enum EncodeError {
@@ -108,6 +242,72 @@ pub enum EncodeError {
     BufferTooSmall,
 
     /// IO error from Stream
+    #[cfg(feature = "std")]
     #[error("Underlying I/O Error: {0}")]
-    IOError(#[from] io::Error),
+    IOError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::{header::ProtocolVersion, payload::NackCode};
+
+    use crate::{DecodeError, DoipCodec, Encoder, ErrorResponse};
+
+    #[test]
+    fn test_decode_error_response() {
+        assert_eq!(
+            DecodeError::FailedProtocolValidation.response(),
+            ErrorResponse::NackAndClose(NackCode::IncorrectPatternFormat)
+        );
+        assert_eq!(
+            DecodeError::UnsupportedProtocolVersion.response(),
+            ErrorResponse::NackAndClose(NackCode::IncorrectPatternFormat)
+        );
+        assert_eq!(
+            DecodeError::InvalidPayloadType.response(),
+            ErrorResponse::Nack(NackCode::UnknownPayloadType)
+        );
+        assert_eq!(
+            DecodeError::MessageTooLarge.response(),
+            ErrorResponse::Nack(NackCode::MessageTooLarge)
+        );
+        assert_eq!(
+            DecodeError::OutOfMemory.response(),
+            ErrorResponse::Nack(NackCode::OutOfMemory)
+        );
+        assert_eq!(
+            DecodeError::TooShort.response(),
+            ErrorResponse::NackAndClose(NackCode::InvalidPayloadLength)
+        );
+        assert_eq!(
+            DecodeError::InvalidPayloadLength.response(),
+            ErrorResponse::NackAndClose(NackCode::InvalidPayloadLength)
+        );
+        assert_eq!(
+            DecodeError::IOError(std::io::ErrorKind::BrokenPipe.into()).response(),
+            ErrorResponse::Close
+        );
+        assert_eq!(
+            DecodeError::InvalidActivationType.response(),
+            ErrorResponse::Discard
+        );
+        assert!(DecodeError::InvalidActivationType.nack_code().is_none());
+    }
+
+    #[test]
+    fn test_decode_error_generic_nack() {
+        let msg = DecodeError::MessageTooLarge
+            .generic_nack(ProtocolVersion::Iso13400_2012)
+            .expect("Message too large is answered with a NACK");
+
+        let mut dst = Vec::<u8>::new();
+        let res = DoipCodec::new().to_bytes(msg, &mut dst);
+
+        assert!(res.is_ok());
+        assert_eq!(*dst, [0x02, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02]);
+
+        assert!(DecodeError::InvalidPowerMode
+            .generic_nack(ProtocolVersion::Iso13400_2012)
+            .is_none());
+    }
 }
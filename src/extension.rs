@@ -0,0 +1,380 @@
+use doip_definitions::{
+    definitions::{
+        DOIP_DIAG_COMMON_SOURCE_LEN, DOIP_DIAG_COMMON_TARGET_LEN, DOIP_DIAG_MESSAGE_ACK_CODE_LEN,
+        DOIP_DIAG_MESSAGE_NACK_CODE_LEN, DOIP_ENTITY_STATUS_RESPONSE_LEN,
+        DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN, DOIP_HEADER_LEN, DOIP_ROUTING_ACTIVATION_REQ_LEN,
+        DOIP_ROUTING_ACTIVATION_RES_LEN,
+    },
+    header::{DoipHeader, PayloadType},
+    message::DoipMessage,
+    payload::DoipPayload,
+};
+
+use alloc::vec::Vec;
+
+use crate::DecodeError;
+
+/// Length of the optional OEM-specific field of routing activation requests
+/// and responses.
+pub const DOIP_ROUTING_ACTIVATION_OEM_LEN: usize = 4;
+
+/// Length of an entity status response payload without the optional max data
+/// size field.
+pub const DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT: usize =
+    DOIP_ENTITY_STATUS_RESPONSE_LEN - DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN;
+
+/// A message carrying optional payload fields which are not part of the
+/// payload types of the `doip-definitions` crate.
+///
+/// The header announces the length of the payload including the extension.
+/// The max data size field of an entity status response is carried here as
+/// well, the field of the same name in the payload is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedMessage {
+    /// The message without its optional fields.
+    pub message: DoipMessage,
+
+    /// The optional fields following the payload.
+    pub extension: PayloadExtension,
+}
+
+/// Optional payload fields defined by ISO 13400-2.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadExtension {
+    /// OEM-specific field of a routing activation request or response, e.g. an
+    /// authentication token.
+    OemSpecific([u8; DOIP_ROUTING_ACTIVATION_OEM_LEN]),
+
+    /// Bytes of the diagnostic message a diagnostic message ACK or NACK refers
+    /// to, as echoed by the `DoIP` entity.
+    PreviousDiagnosticMessage(Vec<u8>),
+
+    /// Maximum size of a logical request the `DoIP` entity accepts, as reported
+    /// in an entity status response.
+    MaxDataSize([u8; DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN]),
+}
+
+impl PayloadExtension {
+    /// Returns the number of bytes the extension adds to the payload.
+    #[must_use]
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            PayloadExtension::OemSpecific(oem_specific) => oem_specific.len(),
+            PayloadExtension::PreviousDiagnosticMessage(previous) => previous.len(),
+            PayloadExtension::MaxDataSize(max_data_size) => max_data_size.len(),
+        }
+    }
+
+    /// Returns `true` if the extension is defined for the payload provided.
+    #[must_use]
+    pub fn is_valid_for(&self, payload: &DoipPayload) -> bool {
+        match self {
+            PayloadExtension::OemSpecific(_) => matches!(
+                payload,
+                DoipPayload::RoutingActivationRequest(_)
+                    | DoipPayload::RoutingActivationResponse(_)
+            ),
+            PayloadExtension::PreviousDiagnosticMessage(_) => matches!(
+                payload,
+                DoipPayload::DiagnosticMessageAck(_) | DoipPayload::DiagnosticMessageNack(_)
+            ),
+            PayloadExtension::MaxDataSize(_) => {
+                matches!(payload, DoipPayload::EntityStatusResponse(_))
+            }
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            PayloadExtension::OemSpecific(oem_specific) => oem_specific,
+            PayloadExtension::PreviousDiagnosticMessage(previous) => previous,
+            PayloadExtension::MaxDataSize(max_data_size) => max_data_size,
+        }
+    }
+
+    /// Decodes the optional fields of a complete frame, based on the payload
+    /// length announced in its header.
+    pub(crate) fn decode(header: &DoipHeader, src: &[u8]) -> Result<Option<Self>, DecodeError> {
+        const DIAG_ACK_LEN: usize = DOIP_DIAG_COMMON_SOURCE_LEN
+            + DOIP_DIAG_COMMON_TARGET_LEN
+            + DOIP_DIAG_MESSAGE_ACK_CODE_LEN;
+        const DIAG_NACK_LEN: usize = DOIP_DIAG_COMMON_SOURCE_LEN
+            + DOIP_DIAG_COMMON_TARGET_LEN
+            + DOIP_DIAG_MESSAGE_NACK_CODE_LEN;
+
+        let (base_len, extension_len) = match header.payload_type {
+            PayloadType::RoutingActivationRequest => (
+                DOIP_ROUTING_ACTIVATION_REQ_LEN,
+                DOIP_ROUTING_ACTIVATION_OEM_LEN,
+            ),
+            PayloadType::RoutingActivationResponse => (
+                DOIP_ROUTING_ACTIVATION_RES_LEN,
+                DOIP_ROUTING_ACTIVATION_OEM_LEN,
+            ),
+            PayloadType::DiagnosticMessageAck => (
+                DIAG_ACK_LEN,
+                (header.payload_length as usize).saturating_sub(DIAG_ACK_LEN),
+            ),
+            PayloadType::DiagnosticMessageNack => (
+                DIAG_NACK_LEN,
+                (header.payload_length as usize).saturating_sub(DIAG_NACK_LEN),
+            ),
+            PayloadType::EntityStatusResponse => (
+                DOIP_ENTITY_STATUS_RESPONSE_LEN_SHORT,
+                DOIP_ENTITY_STATUS_RESPONSE_MDS_LEN,
+            ),
+            _ => return Ok(None),
+        };
+
+        if extension_len == 0 || (header.payload_length as usize) < base_len + extension_len {
+            return Ok(None);
+        }
+
+        let offset = DOIP_HEADER_LEN + base_len;
+        let extension = src
+            .get(offset..offset + extension_len)
+            .ok_or(DecodeError::TooShort)?;
+
+        match header.payload_type {
+            PayloadType::DiagnosticMessageAck | PayloadType::DiagnosticMessageNack => Ok(Some(
+                PayloadExtension::PreviousDiagnosticMessage(extension.to_vec()),
+            )),
+            PayloadType::EntityStatusResponse => Ok(Some(PayloadExtension::MaxDataSize(
+                extension
+                    .try_into()
+                    .expect("Slice is always the correct length"),
+            ))),
+            _ => Ok(Some(PayloadExtension::OemSpecific(
+                extension
+                    .try_into()
+                    .expect("Slice is always the correct length"),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::payload::{
+        ActivationCode, ActivationType, AliveCheckRequest, DiagnosticAckCode, DiagnosticMessageAck,
+        DiagnosticMessageNack, DiagnosticNackCode, DoipPayload, RoutingActivationRequest,
+        RoutingActivationResponse,
+    };
+
+    use crate::{
+        test_util::with_length, Decoder, DoipCodec, DoipFrame, EncodeError, Encoder,
+        ExtendedMessage, PayloadExtension,
+    };
+
+    const ROUTING_ACTIVATION_REQUEST: DoipPayload =
+        DoipPayload::RoutingActivationRequest(RoutingActivationRequest {
+            source_address: [0x0e, 0x00],
+            activation_type: ActivationType::Default,
+            buffer: [0x00, 0x00, 0x00, 0x00],
+        });
+
+    const ROUTING_ACTIVATION_RESPONSE: DoipPayload =
+        DoipPayload::RoutingActivationResponse(RoutingActivationResponse {
+            logical_address: [0x0e, 0x00],
+            source_address: [0x10, 0x00],
+            activation_code: ActivationCode::SuccessfullyActivated,
+            buffer: [0x00, 0x00, 0x00, 0x00],
+        });
+
+    const DIAGNOSTIC_MESSAGE_ACK: DoipPayload =
+        DoipPayload::DiagnosticMessageAck(DiagnosticMessageAck {
+            source_address: [0x10, 0x01],
+            target_address: [0x0e, 0x00],
+            ack_code: DiagnosticAckCode::Acknowledged,
+        });
+
+    const DIAGNOSTIC_MESSAGE_NACK: DoipPayload =
+        DoipPayload::DiagnosticMessageNack(DiagnosticMessageNack {
+            source_address: [0x10, 0x01],
+            target_address: [0x0e, 0x00],
+            nack_code: DiagnosticNackCode::TargetUnreachable,
+        });
+
+    #[test]
+    fn test_routing_activation_request_without_oem_specific() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        let res = codec.to_bytes(with_length(ROUTING_ACTIVATION_REQUEST, 7), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(dst.len(), 15);
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(
+            msg,
+            DoipFrame::Message(with_length(ROUTING_ACTIVATION_REQUEST, 7))
+        );
+    }
+
+    #[test]
+    fn test_routing_activation_request_with_oem_specific() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(ROUTING_ACTIVATION_REQUEST, 11),
+            extension: PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+        };
+
+        assert_eq!(codec.encoded_len(&item), 19);
+
+        let res = codec.to_bytes(item.clone(), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(
+            *dst,
+            [
+                0x02, 0xfd, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0b, 0x0e, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0xde, 0xad, 0xbe, 0xef
+            ]
+        );
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(msg, DoipFrame::Extended(item));
+    }
+
+    #[test]
+    fn test_routing_activation_response_without_oem_specific() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        let res = codec.to_bytes(with_length(ROUTING_ACTIVATION_RESPONSE, 9), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(dst.len(), 17);
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(
+            msg,
+            DoipFrame::Message(with_length(ROUTING_ACTIVATION_RESPONSE, 9))
+        );
+    }
+
+    #[test]
+    fn test_routing_activation_response_with_oem_specific() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(ROUTING_ACTIVATION_RESPONSE, 13),
+            extension: PayloadExtension::OemSpecific([0x01, 0x02, 0x03, 0x04]),
+        };
+
+        let res = codec.to_bytes(DoipFrame::Extended(item.clone()), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(dst.len(), 21);
+        assert_eq!(dst[17..], [0x01, 0x02, 0x03, 0x04]);
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(msg, DoipFrame::Extended(item));
+    }
+
+    #[test]
+    fn test_oem_specific_incomplete() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(ROUTING_ACTIVATION_REQUEST, 11),
+            extension: PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+        };
+
+        let _ = codec.to_bytes(item, &mut dst);
+        let res = codec.decode_from_bytes(&dst[..17]);
+
+        assert!(matches!(res, Ok(None)));
+    }
+
+    #[test]
+    fn test_encode_extension_length_mismatch() {
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(ROUTING_ACTIVATION_REQUEST, 7),
+            extension: PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+        };
+
+        let res = DoipCodec::new().to_bytes(item, &mut dst);
+
+        assert!(matches!(res, Err(EncodeError::PayloadLengthValidation)));
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn test_encode_extension_payload_mismatch() {
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(DoipPayload::AliveCheckRequest(AliveCheckRequest {}), 4),
+            extension: PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+        };
+
+        let res = DoipCodec::new().to_bytes(item, &mut dst);
+
+        assert!(matches!(res, Err(EncodeError::PayloadTypeValidation)));
+    }
+
+    #[test]
+    fn test_diagnostic_message_ack_without_previous_message() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+
+        let res = codec.to_bytes(with_length(DIAGNOSTIC_MESSAGE_ACK, 5), &mut dst);
+        assert!(res.is_ok());
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(
+            msg,
+            DoipFrame::Message(with_length(DIAGNOSTIC_MESSAGE_ACK, 5))
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_message_ack_with_previous_message() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(DIAGNOSTIC_MESSAGE_ACK, 8),
+            extension: PayloadExtension::PreviousDiagnosticMessage(vec![0x22, 0xf1, 0x90]),
+        };
+
+        let res = codec.to_bytes(item.clone(), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(
+            *dst,
+            [
+                0x02, 0xfd, 0x80, 0x02, 0x00, 0x00, 0x00, 0x08, 0x10, 0x01, 0x0e, 0x00, 0x00, 0x22,
+                0xf1, 0x90
+            ]
+        );
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(msg, DoipFrame::Extended(item));
+    }
+
+    #[test]
+    fn test_diagnostic_message_nack_with_previous_message() {
+        let mut codec = DoipCodec::new();
+        let mut dst = Vec::<u8>::new();
+        let item = ExtendedMessage {
+            message: with_length(DIAGNOSTIC_MESSAGE_NACK, 7),
+            extension: PayloadExtension::PreviousDiagnosticMessage(vec![0x10, 0x03]),
+        };
+
+        let res = codec.to_bytes(item.clone(), &mut dst);
+        assert!(res.is_ok());
+        assert_eq!(dst[13..], [0x10, 0x03]);
+
+        let msg = codec.decode_from_bytes(&dst).unwrap().unwrap();
+        assert_eq!(msg, DoipFrame::Extended(item));
+    }
+
+    #[test]
+    fn test_previous_message_incomplete() {
+        let bytes = [
+            0x02, 0xfd, 0x80, 0x02, 0x00, 0x00, 0x00, 0x08, 0x10, 0x01, 0x0e, 0x00, 0x00, 0x22,
+        ];
+
+        let res = DoipCodec::new().decode_from_bytes(&bytes);
+
+        assert!(matches!(res, Ok(None)));
+    }
+}
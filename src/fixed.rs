@@ -0,0 +1,224 @@
+use doip_definitions::{
+    definitions::DOIP_HEADER_LEN,
+    header::{PayloadType, ProtocolVersion},
+};
+
+use crate::{
+    decoder::frame_length, DecodeError, DiagnosticMessageView, DoipCodec, DoipMessageView,
+    EncodeError, FromBytes,
+};
+
+/// A message whose payload is stored inline with a capacity of `N` bytes.
+///
+/// Decoding into a `FixedMessage` never allocates, although the crate as a whole
+/// still requires `alloc`. Payload fields are parsed on access, in the same way
+/// as with a [`DoipMessageView`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedMessage<const N: usize> {
+    protocol_version: ProtocolVersion,
+    payload_type: u16,
+    payload_length: usize,
+    payload: [u8; N],
+}
+
+impl<const N: usize> FixedMessage<N> {
+    /// Creates a message by copying the payload into inline storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EncodeError::BufferTooSmall` if the payload exceeds the capacity
+    /// of `N` bytes.
+    pub fn new(
+        protocol_version: ProtocolVersion,
+        payload_type: u16,
+        payload: &[u8],
+    ) -> Result<Self, EncodeError> {
+        let mut storage = [0u8; N];
+
+        storage
+            .get_mut(..payload.len())
+            .ok_or(EncodeError::BufferTooSmall)?
+            .copy_from_slice(payload);
+
+        Ok(FixedMessage {
+            protocol_version,
+            payload_type,
+            payload_length: payload.len(),
+            payload: storage,
+        })
+    }
+
+    /// Returns the protocol version of the message.
+    #[must_use]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Returns the raw payload type code of the message.
+    #[must_use]
+    pub fn payload_type_code(&self) -> u16 {
+        self.payload_type
+    }
+
+    /// Returns the payload type of the message, if it is defined by ISO 13400-2.
+    #[must_use]
+    pub fn payload_type(&self) -> Option<PayloadType> {
+        PayloadType::from_bytes(&self.payload_type.to_be_bytes())
+    }
+
+    /// Returns the payload bytes of the message.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.payload_length]
+    }
+
+    /// Returns a view over the diagnostic message payload, if this message is a
+    /// diagnostic message.
+    #[must_use]
+    pub fn diagnostic_message(&self) -> Option<DiagnosticMessageView<&[u8]>> {
+        if self.payload_type() != Some(PayloadType::DiagnosticMessage) {
+            return None;
+        }
+
+        DiagnosticMessageView::new(self.payload()).ok()
+    }
+}
+
+impl DoipCodec {
+    /// Decodes the next message of `src` into inline storage without allocating.
+    ///
+    /// Returns the message together with the number of bytes it occupied in
+    /// `src`, or `Ok(None)` if the message has not been received completely.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecodeError::MessageTooLarge` if the payload exceeds the capacity
+    /// of `N` bytes or the configured maximum payload length, and any other
+    /// `DecodeError` if the header is malformed.
+    pub fn decode_fixed<const N: usize>(
+        &self,
+        src: &[u8],
+    ) -> Result<Option<(FixedMessage<N>, usize)>, DecodeError> {
+        if src.len() < DOIP_HEADER_LEN {
+            return Ok(None);
+        }
+
+        self.validate_header(src)?;
+
+        let frame_length = frame_length(src)?;
+        let payload_length = frame_length - DOIP_HEADER_LEN;
+
+        if payload_length > N {
+            return Err(DecodeError::MessageTooLarge);
+        }
+
+        if src.len() < frame_length {
+            return Ok(None);
+        }
+
+        let view = DoipMessageView::new(&src[..frame_length])?;
+        let protocol_version = view.protocol_version()?;
+
+        let message = FixedMessage::new(protocol_version, view.payload_type_code(), view.payload())
+            .map_err(|_| DecodeError::MessageTooLarge)?;
+
+        Ok(Some((message, frame_length)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::header::{PayloadType, ProtocolVersion};
+
+    use crate::{DecodeError, DoipCodec, EncodeError, Encoder, FixedMessage};
+
+    static DIAGNOSTIC_MESSAGE: [u8; 15] = [
+        0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x00, 0x10, 0x01, 0x22, 0xf1, 0x90,
+    ];
+
+    #[test]
+    fn test_decode_fixed() {
+        let codec = DoipCodec::new();
+
+        let (message, consumed) = codec
+            .decode_fixed::<16>(&DIAGNOSTIC_MESSAGE)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(consumed, DIAGNOSTIC_MESSAGE.len());
+        assert_eq!(message.protocol_version(), ProtocolVersion::Iso13400_2012);
+        assert_eq!(message.payload_type(), Some(PayloadType::DiagnosticMessage));
+        assert_eq!(message.payload(), &DIAGNOSTIC_MESSAGE[8..]);
+
+        let diag = message.diagnostic_message().unwrap();
+        assert_eq!(diag.source_address(), [0x0e, 0x00]);
+        assert_eq!(diag.user_data(), &[0x22, 0xf1, 0x90]);
+    }
+
+    #[test]
+    fn test_decode_fixed_incomplete() {
+        let codec = DoipCodec::new();
+
+        let res = codec.decode_fixed::<16>(&DIAGNOSTIC_MESSAGE[..4]);
+        assert!(matches!(res, Ok(None)));
+
+        let res = codec.decode_fixed::<16>(&DIAGNOSTIC_MESSAGE[..12]);
+        assert!(matches!(res, Ok(None)));
+    }
+
+    #[test]
+    fn test_decode_fixed_exceeds_capacity() {
+        let res = DoipCodec::new().decode_fixed::<4>(&DIAGNOSTIC_MESSAGE);
+
+        assert!(matches!(res, Err(DecodeError::MessageTooLarge)));
+    }
+
+    #[test]
+    fn test_decode_fixed_invalid_header() {
+        let mut bytes = DIAGNOSTIC_MESSAGE;
+        bytes[1] = 0xff;
+
+        let res = DoipCodec::new().decode_fixed::<16>(&bytes);
+
+        assert!(matches!(res, Err(DecodeError::FailedProtocolValidation)));
+    }
+
+    #[test]
+    fn test_decode_fixed_header_validation() {
+        let codec = DoipCodec::new().with_supported_versions(&[ProtocolVersion::Iso13400_2019]);
+        let res = codec.decode_fixed::<16>(&DIAGNOSTIC_MESSAGE);
+        assert!(matches!(res, Err(DecodeError::UnsupportedProtocolVersion)));
+
+        let bytes = [0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00];
+        let res = DoipCodec::new()
+            .with_strict_length(true)
+            .decode_fixed::<16>(&bytes);
+        assert!(matches!(res, Err(DecodeError::InvalidPayloadLength)));
+    }
+
+    #[test]
+    fn test_fixed_message_too_large() {
+        let res = FixedMessage::<2>::new(ProtocolVersion::Iso13400_2012, 0x8001, &[0; 3]);
+
+        assert!(matches!(res, Err(EncodeError::BufferTooSmall)));
+    }
+
+    #[test]
+    fn test_encode_fixed_to_slice() {
+        let mut codec = DoipCodec::new();
+        let mut dst = [0u8; 15];
+        let (message, _) = codec
+            .decode_fixed::<16>(&DIAGNOSTIC_MESSAGE)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(codec.encoded_len(&message), 15);
+
+        let res = codec.encode_to_slice(message.clone(), &mut dst);
+        assert!(matches!(res, Ok(15)));
+        assert_eq!(dst, DIAGNOSTIC_MESSAGE);
+
+        let res = codec.encode_to_slice(message, &mut dst[..14]);
+        assert!(matches!(res, Err(EncodeError::BufferTooSmall)));
+    }
+}
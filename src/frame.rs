@@ -0,0 +1,73 @@
+use alloc::vec::Vec;
+use doip_definitions::{header::ProtocolVersion, message::DoipMessage};
+
+use crate::{CustomMessage, ExtendedMessage};
+
+/// A single frame as produced by the `DoipCodec` decoder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DoipFrame {
+    /// A message with one of the payload types defined by ISO 13400-2.
+    Message(DoipMessage),
+
+    /// A message with an OEM-specific or otherwise unknown payload type, or with
+    /// a payload holding unknown values when decoding leniently, passed through
+    /// without interpreting its payload.
+    Raw(RawMessage),
+
+    /// A message decoded by a registered `CustomPayloadCodec`.
+    Custom(CustomMessage),
+
+    /// A message with optional payload fields, such as the OEM-specific field
+    /// of a routing activation.
+    Extended(ExtendedMessage),
+}
+
+impl From<DoipMessage> for DoipFrame {
+    fn from(value: DoipMessage) -> Self {
+        DoipFrame::Message(value)
+    }
+}
+
+impl From<RawMessage> for DoipFrame {
+    fn from(value: RawMessage) -> Self {
+        DoipFrame::Raw(value)
+    }
+}
+
+impl From<CustomMessage> for DoipFrame {
+    fn from(value: CustomMessage) -> Self {
+        DoipFrame::Custom(value)
+    }
+}
+
+impl From<ExtendedMessage> for DoipFrame {
+    fn from(value: ExtendedMessage) -> Self {
+        DoipFrame::Extended(value)
+    }
+}
+
+/// A message whose payload type is not known to the codec, such as the
+/// OEM-specific payload types in the range `0xF000..=0xFFFF`.
+///
+/// The inverse protocol version and payload length of the header are derived
+/// from the other fields when encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawMessage {
+    /// Protocol version of the message.
+    pub protocol_version: ProtocolVersion,
+
+    /// Raw payload type code as found in the header.
+    pub payload_type: u16,
+
+    /// Uninterpreted payload bytes.
+    pub payload: Vec<u8>,
+}
+
+impl RawMessage {
+    /// Returns `true` if the payload type lies within the range reserved for
+    /// OEM-specific payload types.
+    #[must_use]
+    pub fn is_oem_specific(&self) -> bool {
+        self.payload_type >= 0xF000
+    }
+}
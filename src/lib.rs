@@ -1,27 +1,355 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(clippy::pedantic)]
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
+#![cfg_attr(test, allow(clippy::unnecessary_mut_passed))]
 //! # Diagnostics over Internet Protocol Codec Crate
 //!
 //! The purpose of this crate is to provide an easy way to encode and decode
 //! `DoIP` Messages defined in the `doip-definitions` crate.
 //!
+//! ## Features
 //!
+//! - `std` (default): enables the `tokio_util` codec implementations, I/O
+//!   errors, the asynchronous [`DoipClient`] and [`DoipServer`] and vehicle
+//!   discovery via [`discover`]. Without it the crate is `no_std`, but still
+//!   requires `alloc` and a global allocator, as do the message types of
+//!   `doip-definitions`. [`DoipCodec::encode_to_slice`] and
+//!   [`DoipCodec::decode_fixed`] do not allocate themselves, which keeps the
+//!   heap out of the hot path.
+//! - `tls`: enables `DoIP` over TLS on port 3496 using rustls, for both the
+//!   client and the server. A client configured for TLS reconnects over TLS
+//!   when an entity requests an encrypted connection.
+//!
+
+extern crate alloc;
 
+mod address;
+#[cfg(feature = "std")]
+mod client;
+mod context;
+mod custom_payload;
+mod datagram;
 mod decoder;
+mod describe;
+#[cfg(feature = "std")]
+mod discovery;
+mod dissect;
 mod doip_message;
 mod encoder;
 mod error;
+mod extension;
+mod fixed;
+mod frame;
+#[cfg(feature = "std")]
+mod server;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "tls")]
+mod tls;
+mod validate;
+mod view;
 
+pub use crate::address::*;
+#[cfg(feature = "std")]
+pub use crate::client::*;
+pub use crate::context::*;
+pub use crate::custom_payload::*;
+pub use crate::datagram::*;
+pub use crate::describe::Describe;
+#[cfg(feature = "std")]
+pub use crate::discovery::*;
+pub use crate::dissect::*;
 pub use crate::error::*;
+pub use crate::extension::*;
+pub use crate::fixed::*;
+pub use crate::frame::*;
+#[cfg(feature = "std")]
+pub use crate::server::*;
+#[cfg(feature = "tls")]
+pub use crate::tls::*;
+pub use crate::validate::*;
+pub use crate::view::*;
+
+use crate::custom_payload::PayloadRegistry;
+use alloc::vec::Vec;
+use bytes::BufMut;
+use doip_definitions::header::ProtocolVersion;
 
 /// A simple Decoder and Encoder implementation for Diagnostics over Internet
 /// Protocol.
 ///
 /// Can be used independently via `encode` and `decode` methods, however is best
 /// utilised during.
+///
+/// Decoding returns `Ok(None)` until the header and the full payload it
+/// announces have been received, payloads are never decoded from partial data.
+///
+/// The codec enforces two limits on the `payload_length` announced by a peer.
+/// Messages exceeding the maximum processable length are rejected with
+/// [`DecodeError::MessageTooLarge`], messages exceeding the currently available
+/// memory with [`DecodeError::OutOfMemory`]. In both cases the offending bytes
+/// are discarded so that a server can answer with the matching `GenericNack`
+/// and carry on with the next message.
+///
+/// How the codec deals with any other malformed message is controlled by its
+/// [`RecoveryPolicy`].
+///
+/// Messages with OEM-specific or unknown payload types are rejected with
+/// [`DecodeError::InvalidPayloadType`] unless raw pass-through is enabled, in
+/// which case they are decoded into [`DoipFrame::Raw`]. Payload types with a
+/// registered [`CustomPayloadCodec`] are decoded into [`DoipFrame::Custom`].
+///
+/// Any known protocol version is accepted unless a set of supported versions
+/// is configured. Independent of that set, the default protocol version `0xFF`
+/// is only accepted for vehicle identification requests. Violations are
+/// reported as [`DecodeError::UnsupportedProtocolVersion`].
+///
+/// By default a payload may be followed by bytes the codec does not know
+/// about, which are skipped. In strict length mode the announced payload length
+/// has to match one of the lengths ISO 13400-2 allows for the payload type,
+/// otherwise [`DecodeError::InvalidPayloadLength`] is returned.
+///
+/// Payloads holding values this crate does not know, such as reserved or
+/// vendor-specific NACK codes or power modes, fail to decode unless lenient
+/// decoding is enabled. In that case the whole message is decoded into
+/// [`DoipFrame::Raw`] and re-encoded byte for byte. Lenient decoding works on
+/// the message level: none of the fields of such a message are parsed, not
+/// even the known ones such as its addresses.
+///
+/// Semantic validation is opt-in. With a [`Validator`] set, every message the
+/// codec decodes or encodes is checked and the rules it violates are collected
+/// by the codec, without affecting the result of decoding or encoding.
+///
+/// The [`DecodeError`]s of the codec only tell the kind of error. The offset
+/// and value of the offending field are only kept by
+/// [`DoipCodec::decode_detailed`] and by a [`DetailedCodec`] on streams.
 #[derive(Debug)]
-pub struct DoipCodec {}
+pub struct DoipCodec {
+    max_payload_length: u32,
+    available_memory: u32,
+    recovery_policy: RecoveryPolicy,
+    raw_pass_through: bool,
+    custom_payloads: PayloadRegistry,
+    supported_versions: Option<Vec<ProtocolVersion>>,
+    strict_length: bool,
+    lenient: bool,
+    validator: Option<Validator>,
+    violations: Vec<Violation>,
+    discard_remaining: usize,
+}
+
+/// Determines how the stream decoder continues after a message failed to
+/// decode.
+///
+/// The decode error is always returned to the caller, the policy only decides
+/// which bytes are dropped from the read buffer so that subsequent calls do not
+/// trip over the same malformed message again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryPolicy {
+    /// Leave the read buffer untouched. Further decoding will fail with the
+    /// same error, the connection is expected to be closed.
+    #[default]
+    Fatal,
+
+    /// Drop the malformed message using the payload length announced in its
+    /// header. If the header itself is invalid its length cannot be trusted and
+    /// the decoder falls back to [`RecoveryPolicy::Resync`].
+    SkipFrame,
+
+    /// Drop bytes until the next valid protocol version / inverse protocol
+    /// version pair is found.
+    Resync,
+}
+
+impl DoipCodec {
+    /// Creates a new codec which accepts any announced payload length.
+    #[must_use]
+    pub fn new() -> Self {
+        DoipCodec {
+            max_payload_length: u32::MAX,
+            available_memory: u32::MAX,
+            recovery_policy: RecoveryPolicy::Fatal,
+            raw_pass_through: false,
+            custom_payloads: PayloadRegistry::default(),
+            supported_versions: None,
+            strict_length: false,
+            lenient: false,
+            validator: None,
+            violations: Vec::new(),
+            discard_remaining: 0,
+        }
+    }
+
+    /// Sets the maximum payload length this codec is able to process.
+    #[must_use]
+    pub fn with_max_payload_length(mut self, max_payload_length: u32) -> Self {
+        self.max_payload_length = max_payload_length;
+        self
+    }
+
+    /// Sets the amount of memory, in bytes, currently available for buffering a
+    /// single payload.
+    #[must_use]
+    pub fn with_available_memory(mut self, available_memory: u32) -> Self {
+        self.available_memory = available_memory;
+        self
+    }
+
+    /// Sets the policy used to recover from malformed messages.
+    #[must_use]
+    pub fn with_recovery_policy(mut self, recovery_policy: RecoveryPolicy) -> Self {
+        self.recovery_policy = recovery_policy;
+        self
+    }
+
+    /// Enables or disables passing through messages with OEM-specific or
+    /// unknown payload types as [`RawMessage`]s.
+    #[must_use]
+    pub fn with_raw_pass_through(mut self, raw_pass_through: bool) -> Self {
+        self.raw_pass_through = raw_pass_through;
+        self
+    }
+
+    /// Restricts the protocol versions accepted by the decoder. The default
+    /// protocol version `0xFF` does not need to be listed.
+    #[must_use]
+    pub fn with_supported_versions(mut self, supported_versions: &[ProtocolVersion]) -> Self {
+        self.supported_versions = Some(supported_versions.to_vec());
+        self
+    }
+
+    /// Enables or disables checking the announced payload length against the
+    /// lengths allowed for each payload type.
+    #[must_use]
+    pub fn with_strict_length(mut self, strict_length: bool) -> Self {
+        self.strict_length = strict_length;
+        self
+    }
+
+    /// Enables or disables passing through messages whose payload holds unknown
+    /// values as [`RawMessage`]s instead of failing to decode them.
+    ///
+    /// This covers values of `NackCode`, `ActionCode`, `SyncStatus`,
+    /// `ActivationType`, `ActivationCode`, `NodeType`, `PowerMode`,
+    /// `DiagnosticAckCode` and `DiagnosticNackCode`. As these enums cannot hold
+    /// unknown values, the whole message is passed through unparsed rather than
+    /// just the offending field, so a typed message carrying the raw code is
+    /// never produced. Re-encoding the raw message reproduces the received
+    /// bytes.
+    #[must_use]
+    pub fn with_lenient_decoding(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets the validator every decoded and encoded message is checked with.
+    /// The violations found are collected until taken with
+    /// [`DoipCodec::take_violations`].
+    ///
+    /// [`DoipCodec::decode_detailed`] borrows the codec immutably and does not
+    /// validate, use a [`DetailedCodec`] instead.
+    #[must_use]
+    pub fn with_validator(mut self, validator: Validator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Registers a codec for an application defined payload type, replacing any
+    /// codec previously registered for the same code.
+    #[must_use]
+    pub fn with_custom_payload<C: CustomPayloadCodec>(
+        mut self,
+        payload_type: u16,
+        codec: C,
+    ) -> Self {
+        self.register_custom_payload(payload_type, codec);
+        self
+    }
+
+    /// Registers a codec for an application defined payload type, replacing any
+    /// codec previously registered for the same code.
+    pub fn register_custom_payload<C: CustomPayloadCodec>(&mut self, payload_type: u16, codec: C) {
+        self.custom_payloads.register(payload_type, codec);
+    }
+
+    /// Returns the maximum payload length this codec is able to process.
+    #[must_use]
+    pub fn max_payload_length(&self) -> u32 {
+        self.max_payload_length
+    }
+
+    /// Returns the amount of memory, in bytes, available for buffering a single
+    /// payload.
+    #[must_use]
+    pub fn available_memory(&self) -> u32 {
+        self.available_memory
+    }
+
+    /// Returns the policy used to recover from malformed messages.
+    #[must_use]
+    pub fn recovery_policy(&self) -> RecoveryPolicy {
+        self.recovery_policy
+    }
+
+    /// Returns `true` if messages with unknown payload types are passed through.
+    #[must_use]
+    pub fn raw_pass_through(&self) -> bool {
+        self.raw_pass_through
+    }
+
+    /// Returns the protocol versions accepted by the decoder, `None` if any
+    /// known version is accepted.
+    #[must_use]
+    pub fn supported_versions(&self) -> Option<&[ProtocolVersion]> {
+        self.supported_versions.as_deref()
+    }
+
+    /// Returns `true` if announced payload lengths are checked against the
+    /// lengths allowed for each payload type.
+    #[must_use]
+    pub fn strict_length(&self) -> bool {
+        self.strict_length
+    }
+
+    /// Returns `true` if messages whose payload holds unknown values are passed
+    /// through.
+    #[must_use]
+    pub fn lenient_decoding(&self) -> bool {
+        self.lenient
+    }
+
+    /// Returns the validator messages are checked with, if any.
+    #[must_use]
+    pub fn validator(&self) -> Option<Validator> {
+        self.validator
+    }
+
+    /// Returns the violations found since they were last taken.
+    #[must_use]
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Returns the violations found since they were last taken and clears them.
+    /// Taking them after every item associates them with the message they were
+    /// found in.
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        core::mem::take(&mut self.violations)
+    }
+
+    /// Updates the amount of memory available for buffering a single payload,
+    /// e.g. when the application frees or claims receive buffers at runtime.
+    pub fn set_available_memory(&mut self, available_memory: u32) {
+        self.available_memory = available_memory;
+    }
+}
+
+impl Default for DoipCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Decoder trait to decode inbound messages from a source and produce human-readable and programmable
 /// output. Similar but adapted from the `tokio_utils` Decoder to be used within a `no_std` environment.
@@ -35,6 +363,10 @@ pub trait Decoder {
     type Error: From<DecodeError>;
 
     /// Attempts to decode a frame from the provided buffer of bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Self::Error` if the bytes could not be decoded into a valid frame.
     fn decode_from_bytes(&mut self, src: &[u8]) -> Result<Option<Self::Item>, Self::Error>;
 }
 
@@ -44,8 +376,16 @@ pub trait Encoder<Item> {
     /// The type of encoding errors.
     type Error: From<EncodeError>;
 
+    /// Returns the exact number of bytes `to_bytes` writes for the item, so the
+    /// destination can be reserved up front.
+    fn encoded_len(&self, item: &Item) -> usize;
+
     /// Encodes a frame into the buffer provided.
-    fn to_bytes(&mut self, item: Item, dst: &mut Vec<u8>) -> Result<(), Self::Error>;
+    ///
+    /// # Errors
+    ///
+    /// Returns `Self::Error` if the item could not be encoded.
+    fn to_bytes<B: BufMut + ?Sized>(&mut self, item: Item, dst: &mut B) -> Result<(), Self::Error>;
 }
 
 trait ToBytes {
@@ -0,0 +1,1364 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::Poll,
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use doip_definitions::{
+    definitions::DOIP_ROUTING_ACTIVATION_REQ_SRC_LEN,
+    header::{PayloadType, ProtocolVersion},
+    message::DoipMessage,
+    payload::{
+        ActionCode, ActivationCode, ActivationType, AliveCheckRequest, DiagnosticAckCode,
+        DiagnosticMessage, DiagnosticMessageAck, DiagnosticMessageNack, DiagnosticNackCode,
+        DoipPayload, GenericNack, NodeType, PowerInformationResponse, PowerMode,
+        RoutingActivationRequest, RoutingActivationResponse, VehicleAnnouncementMessage,
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UdpSocket},
+    sync::Notify,
+    time::{timeout, timeout_at, Instant},
+};
+
+use crate::{
+    DecodeError, DoipCodec, DoipFrame, EncodeError, ExtendedMessage, LogicalAddress, RawMessage,
+    RecoveryPolicy,
+};
+
+/// Time a socket stays open without routing being activated,
+/// `T_TCP_Initial_Inactivity` of ISO 13400-2.
+pub const T_TCP_INITIAL_INACTIVITY: Duration = Duration::from_secs(2);
+
+/// Time a tester has to answer an alive check request before its source
+/// address is activated on another socket, `T_TCP_Alive_Check` of ISO 13400-2.
+pub const T_TCP_ALIVE_CHECK: Duration = Duration::from_millis(500);
+
+/// Time a socket with activated routing stays open without receiving a
+/// message, `T_TCP_General_Inactivity` of ISO 13400-2.
+// `Duration::from_mins` would raise the minimum supported Rust version
+#[allow(clippy::duration_suboptimal_units)]
+pub const T_TCP_GENERAL_INACTIVITY: Duration = Duration::from_secs(300);
+
+/// Largest datagram read by [`DoipServer::serve_udp`].
+const MAX_DATAGRAM_LEN: usize = 1500;
+
+/// Errors raised while serving a connection of a [`DoipServer`].
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    /// IO error from the connection
+    #[error("Underlying I/O Error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    /// response failed to encode
+    #[error("failed to encode message: {0}")]
+    Encode(#[from] EncodeError),
+}
+
+/// Decides whether routing is activated for a tester.
+///
+/// Implemented for closures taking the source address and activation type of
+/// the request.
+pub trait RoutingActivationHandler: Send + Sync {
+    /// Returns the activation code to respond with,
+    /// `ActivationCode::SuccessfullyActivated` to activate routing.
+    fn activate(
+        &self,
+        source_address: LogicalAddress,
+        activation_type: ActivationType,
+    ) -> ActivationCode;
+}
+
+impl<F> RoutingActivationHandler for F
+where
+    F: Fn(LogicalAddress, ActivationType) -> ActivationCode + Send + Sync,
+{
+    fn activate(
+        &self,
+        source_address: LogicalAddress,
+        activation_type: ActivationType,
+    ) -> ActivationCode {
+        self(source_address, activation_type)
+    }
+}
+
+/// Future resolving to the user data of a diagnostic response, `None` if no
+/// response is sent.
+pub type DiagnosticResponse<'a> = Pin<Box<dyn Future<Output = Option<Vec<u8>>> + Send + 'a>>;
+
+/// Processes the diagnostic messages sent to a target address.
+///
+/// The message is acknowledged before the handler runs, so the response may
+/// take as long as the ECU needs, e.g. when a gateway forwards the request to
+/// a sub-network. Implemented for closures taking the source address and user
+/// data of the message and returning a future.
+pub trait DiagnosticHandler: Send + Sync {
+    /// Returns the user data of the diagnostic response, `None` if no response
+    /// is sent.
+    fn handle(&self, source_address: LogicalAddress, data: Vec<u8>) -> DiagnosticResponse<'_>;
+}
+
+impl<F, Fut> DiagnosticHandler for F
+where
+    F: Fn(LogicalAddress, Vec<u8>) -> Fut + Send + Sync,
+    Fut: Future<Output = Option<Vec<u8>>> + Send + 'static,
+{
+    fn handle(&self, source_address: LogicalAddress, data: Vec<u8>) -> DiagnosticResponse<'_> {
+        Box::pin(self(source_address, data))
+    }
+}
+
+/// Configuration of a [`DoipServer`], reported in its vehicle announcements,
+/// entity status and power information responses.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    logical_address: LogicalAddress,
+    node_type: NodeType,
+    max_concurrent_sockets: u8,
+    max_data_size: Option<u32>,
+    power_mode: PowerMode,
+    protocol_version: ProtocolVersion,
+    vin: [u8; 17],
+    eid: [u8; 6],
+    gid: [u8; 6],
+    initial_inactivity_timeout: Duration,
+    general_inactivity_timeout: Duration,
+    alive_check_timeout: Duration,
+    tls_required: bool,
+}
+
+impl ServerConfig {
+    /// Creates a configuration for a `DoIP` node with the given logical
+    /// address, accepting a single socket.
+    #[must_use]
+    pub fn new(logical_address: LogicalAddress) -> Self {
+        ServerConfig {
+            logical_address,
+            node_type: NodeType::DoipNode,
+            max_concurrent_sockets: 1,
+            max_data_size: None,
+            power_mode: PowerMode::Ready,
+            protocol_version: ProtocolVersion::Iso13400_2012,
+            vin: [0; 17],
+            eid: [0; 6],
+            gid: [0; 6],
+            initial_inactivity_timeout: T_TCP_INITIAL_INACTIVITY,
+            general_inactivity_timeout: T_TCP_GENERAL_INACTIVITY,
+            alive_check_timeout: T_TCP_ALIVE_CHECK,
+            tls_required: false,
+        }
+    }
+
+    /// Sets the node type of the entity.
+    #[must_use]
+    pub fn with_node_type(mut self, node_type: NodeType) -> Self {
+        self.node_type = node_type;
+        self
+    }
+
+    /// Sets the number of sockets the entity accepts concurrently.
+    #[must_use]
+    pub fn with_max_concurrent_sockets(mut self, max_concurrent_sockets: u8) -> Self {
+        self.max_concurrent_sockets = max_concurrent_sockets;
+        self
+    }
+
+    /// Sets the largest payload the entity processes. Larger messages are
+    /// answered with a generic NACK.
+    #[must_use]
+    pub fn with_max_data_size(mut self, max_data_size: u32) -> Self {
+        self.max_data_size = Some(max_data_size);
+        self
+    }
+
+    /// Sets the power mode reported in power information responses.
+    #[must_use]
+    pub fn with_power_mode(mut self, power_mode: PowerMode) -> Self {
+        self.power_mode = power_mode;
+        self
+    }
+
+    /// Sets the protocol version of outgoing messages.
+    #[must_use]
+    pub fn with_protocol_version(mut self, protocol_version: ProtocolVersion) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// Sets the identification reported in vehicle announcements.
+    #[must_use]
+    pub fn with_identification(mut self, vin: [u8; 17], eid: [u8; 6], gid: [u8; 6]) -> Self {
+        self.vin = vin;
+        self.eid = eid;
+        self.gid = gid;
+        self
+    }
+
+    /// Sets how long a socket stays open without routing being activated.
+    #[must_use]
+    pub fn with_initial_inactivity_timeout(mut self, initial_inactivity_timeout: Duration) -> Self {
+        self.initial_inactivity_timeout = initial_inactivity_timeout;
+        self
+    }
+
+    /// Sets how long a socket with activated routing stays open without
+    /// receiving a message.
+    #[must_use]
+    pub fn with_general_inactivity_timeout(mut self, general_inactivity_timeout: Duration) -> Self {
+        self.general_inactivity_timeout = general_inactivity_timeout;
+        self
+    }
+
+    /// Sets how long a tester has to answer an alive check request before its
+    /// source address is activated on another socket.
+    #[must_use]
+    pub fn with_alive_check_timeout(mut self, alive_check_timeout: Duration) -> Self {
+        self.alive_check_timeout = alive_check_timeout;
+        self
+    }
+
+    /// Enables or disables denying routing activation on unencrypted sockets
+    /// with `ActivationCode::DeniedRequestEncryptedTLSConnection`, asking
+    /// testers to reconnect over TLS.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn with_tls_required(mut self, tls_required: bool) -> Self {
+        self.tls_required = tls_required;
+        self
+    }
+
+    /// Returns the logical address of the entity.
+    #[must_use]
+    pub fn logical_address(&self) -> LogicalAddress {
+        self.logical_address
+    }
+
+    /// Returns the node type of the entity.
+    #[must_use]
+    pub fn node_type(&self) -> NodeType {
+        self.node_type
+    }
+
+    /// Returns the number of sockets the entity accepts concurrently.
+    #[must_use]
+    pub fn max_concurrent_sockets(&self) -> u8 {
+        self.max_concurrent_sockets
+    }
+
+    /// Returns the largest payload the entity processes, if limited.
+    #[must_use]
+    pub fn max_data_size(&self) -> Option<u32> {
+        self.max_data_size
+    }
+
+    /// Returns the power mode reported in power information responses.
+    #[must_use]
+    pub fn power_mode(&self) -> PowerMode {
+        self.power_mode
+    }
+
+    /// Returns the protocol version of outgoing messages.
+    #[must_use]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Returns how long a socket stays open without routing being activated.
+    #[must_use]
+    pub fn initial_inactivity_timeout(&self) -> Duration {
+        self.initial_inactivity_timeout
+    }
+
+    /// Returns how long a socket with activated routing stays open without
+    /// receiving a message.
+    #[must_use]
+    pub fn general_inactivity_timeout(&self) -> Duration {
+        self.general_inactivity_timeout
+    }
+
+    /// Returns how long a tester has to answer an alive check request before
+    /// its source address is activated on another socket.
+    #[must_use]
+    pub fn alive_check_timeout(&self) -> Duration {
+        self.alive_check_timeout
+    }
+
+    /// Returns `true` if routing is only activated on TLS sockets.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn tls_required(&self) -> bool {
+        self.tls_required
+    }
+}
+
+/// State shared between the sockets of a server.
+struct Shared {
+    config: ServerConfig,
+    activation_handler: Box<dyn RoutingActivationHandler>,
+    targets: HashMap<LogicalAddress, Arc<dyn DiagnosticHandler>>,
+    open_sockets: AtomicU8,
+    active_sources: Mutex<HashMap<LogicalAddress, Arc<AliveCheck>>>,
+}
+
+impl Shared {
+    /// Locks the alive checks of the sockets, by the source address active on
+    /// them.
+    fn active_sources(&self) -> MutexGuard<'_, HashMap<LogicalAddress, Arc<AliveCheck>>> {
+        self.active_sources
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A `DoIP` entity serving testers over TCP and UDP.
+///
+/// Routing activation requests are passed to a [`RoutingActivationHandler`].
+/// A source address already active on another socket is only activated again
+/// if the tester on that socket does not answer an alive check request.
+/// Diagnostic messages from an activated tester are acknowledged and passed to
+/// the [`DiagnosticHandler`] registered for their target address, or rejected
+/// with a diagnostic message NACK. Entity status and power information
+/// requests are answered from the [`ServerConfig`], malformed messages with the
+/// generic NACK ISO 13400-2 requires.
+#[derive(Clone)]
+pub struct DoipServer {
+    shared: Arc<Shared>,
+}
+
+impl DoipServer {
+    /// Creates a server without any target addresses.
+    pub fn new<H>(config: ServerConfig, activation_handler: H) -> Self
+    where
+        H: RoutingActivationHandler + 'static,
+    {
+        Self::builder(config, activation_handler).build()
+    }
+
+    /// Starts building a server, see [`DoipServerBuilder::with_target`] to
+    /// register target addresses.
+    pub fn builder<H>(config: ServerConfig, activation_handler: H) -> DoipServerBuilder
+    where
+        H: RoutingActivationHandler + 'static,
+    {
+        DoipServerBuilder {
+            config,
+            activation_handler: Box::new(activation_handler),
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Returns the configuration of the server.
+    #[must_use]
+    pub fn config(&self) -> &ServerConfig {
+        &self.shared.config
+    }
+
+    /// Returns the number of sockets currently open.
+    #[must_use]
+    pub fn open_sockets(&self) -> u8 {
+        self.shared.open_sockets.load(Ordering::Relaxed)
+    }
+
+    /// Accepts connections until the listener fails, serving each on its own
+    /// task. Connections which cannot be set up are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the listener.
+    pub async fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            if stream.set_nodelay(true).is_err() {
+                continue;
+            }
+
+            let server = self.clone();
+            tokio::spawn(async move { server.handle_connection(stream).await });
+        }
+    }
+
+    /// Serves a single unencrypted connection until the tester closes it or a
+    /// message requires closing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ServerError::IOError` if the connection failed.
+    pub async fn handle_connection<S>(&self, stream: S) -> Result<(), ServerError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.serve_connection(stream, false).await
+    }
+
+    pub(crate) async fn serve_connection<S>(
+        &self,
+        stream: S,
+        encrypted: bool,
+    ) -> Result<(), ServerError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        // Lenient decoding passes on requests with a reserved activation type,
+        // which are denied rather than discarded
+        let mut codec = DoipCodec::new()
+            .with_recovery_policy(RecoveryPolicy::SkipFrame)
+            .with_lenient_decoding(true);
+        if let Some(max_data_size) = self.shared.config.max_data_size {
+            codec = codec.with_max_payload_length(max_data_size);
+        }
+
+        let Some(mut socket) = Socket::open(Arc::clone(&self.shared), encrypted) else {
+            // No more sockets can be counted, the connection is refused
+            return Ok(());
+        };
+        let mut connection = Connection {
+            io: stream,
+            codec,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+        };
+
+        let opened = Instant::now();
+        let alive_check = Arc::clone(&socket.alive_check);
+
+        loop {
+            let config = &self.shared.config;
+            let deadline = match socket.active_source {
+                None => opened + config.initial_inactivity_timeout,
+                Some(_) => Instant::now() + config.general_inactivity_timeout,
+            };
+
+            let frame = match connection.wait(deadline, &alive_check).await {
+                Wakeup::Read(frame) => frame,
+                Wakeup::AliveCheck => {
+                    let req = DoipPayload::AliveCheckRequest(AliveCheckRequest {});
+                    connection.send(self.message(req)?).await?;
+                    continue;
+                }
+                Wakeup::Inactive | Wakeup::Replaced => return Ok(()),
+            };
+
+            let message = match frame {
+                // Optional fields such as the OEM-specific field of a routing
+                // activation request are not passed to the handlers
+                Ok(Some(
+                    DoipFrame::Message(message)
+                    | DoipFrame::Extended(ExtendedMessage { message, .. }),
+                )) => message,
+                Ok(Some(DoipFrame::Raw(raw))) => {
+                    if let Some(reply) = self.respond_raw(&raw)? {
+                        // Only sent to deny routing activation, which closes
+                        // the socket
+                        connection.send(reply).await?;
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => return Ok(()),
+                Err(DecodeError::IOError(err)) => return Err(err.into()),
+                Err(err) => {
+                    let response = err.response();
+                    if let Some(nack_code) = response.nack_code() {
+                        let nack = DoipPayload::GenericNack(GenericNack { nack_code });
+                        connection.send(self.message(nack)?).await?;
+                    }
+                    if response.closes_socket() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+
+            if self
+                .respond(&mut socket, &mut connection, message.payload)
+                .await?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Answers vehicle identification, entity status and power information
+    /// requests received on a UDP socket until it fails. Replies which cannot
+    /// be sent, e.g. to an unreachable peer, are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ServerError::IOError` if receiving from the socket failed.
+    pub async fn serve_udp(&self, socket: UdpSocket) -> Result<(), ServerError> {
+        let mut codec = DoipCodec::new();
+        let mut datagram = [0; MAX_DATAGRAM_LEN];
+        let mut buf = BytesMut::new();
+
+        loop {
+            let (len, peer) = socket.recv_from(&mut datagram).await?;
+
+            let Ok(DoipFrame::Message(message)) = codec.decode_datagram(&datagram[..len]) else {
+                continue;
+            };
+            let Some(reply) = self.respond_udp(message.payload)? else {
+                continue;
+            };
+
+            tokio_util::codec::Encoder::encode(&mut codec, reply, &mut buf)?;
+            // One unreachable peer must not end discovery for all others
+            let _ = socket.send_to(&buf, peer).await;
+            buf.clear();
+        }
+    }
+
+    /// Sends the replies to a message, returning whether to close the socket
+    /// afterwards.
+    async fn respond<S>(
+        &self,
+        socket: &mut Socket,
+        connection: &mut Connection<S>,
+        payload: DoipPayload,
+    ) -> Result<bool, ServerError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let config = &self.shared.config;
+        let entity_address = config.logical_address.to_bytes();
+
+        match payload {
+            DoipPayload::RoutingActivationRequest(req) => {
+                let activation_code = socket.activate(&req).await;
+                let res = DoipPayload::RoutingActivationResponse(RoutingActivationResponse {
+                    logical_address: req.source_address,
+                    source_address: entity_address,
+                    activation_code,
+                    buffer: [0; 4],
+                });
+                connection.send(self.message(res)?).await?;
+
+                Ok(closes_socket(activation_code))
+            }
+            DoipPayload::DiagnosticMessage(msg) => {
+                let source_address = LogicalAddress::from(msg.source_address);
+                let target_address = LogicalAddress::from(msg.target_address);
+                let nack = |nack_code| {
+                    DoipPayload::DiagnosticMessageNack(DiagnosticMessageNack {
+                        source_address: msg.target_address,
+                        target_address: msg.source_address,
+                        nack_code,
+                    })
+                };
+
+                if socket.active_source != Some(source_address) {
+                    let nack = nack(DiagnosticNackCode::InvalidSourceAddress);
+                    connection.send(self.message(nack)?).await?;
+                    return Ok(true);
+                }
+
+                let Some(handler) = self.shared.targets.get(&target_address) else {
+                    let nack = nack(DiagnosticNackCode::UnknownTargetAddress);
+                    connection.send(self.message(nack)?).await?;
+                    return Ok(false);
+                };
+
+                // Acknowledged before the handler runs, which may wait for an
+                // ECU far longer than A_DoIP_Diagnostic_Message
+                let ack = DoipPayload::DiagnosticMessageAck(DiagnosticMessageAck {
+                    source_address: msg.target_address,
+                    target_address: msg.source_address,
+                    ack_code: DiagnosticAckCode::Acknowledged,
+                });
+                connection.send(self.message(ack)?).await?;
+
+                if let Some(data) = handler.handle(source_address, msg.message).await {
+                    let res = DoipPayload::DiagnosticMessage(DiagnosticMessage {
+                        source_address: msg.target_address,
+                        target_address: msg.source_address,
+                        message: data,
+                    });
+                    connection.send(self.message(res)?).await?;
+                }
+
+                Ok(false)
+            }
+            DoipPayload::AliveCheckResponse(res) => {
+                if socket.active_source == Some(LogicalAddress::from(res.source_address)) {
+                    socket.alive_check.answered.notify_waiters();
+                }
+
+                Ok(false)
+            }
+            payload => {
+                if let Some(reply) = self.respond_udp(payload)? {
+                    connection.send(reply).await?;
+                }
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Returns the reply to a message holding unknown values. Routing activation
+    /// requests with a reserved activation type are denied, after which the
+    /// socket is closed, all other messages are discarded.
+    fn respond_raw(&self, raw: &RawMessage) -> Result<Option<DoipMessage>, ServerError> {
+        if raw.payload_type != PayloadType::RoutingActivationRequest as u16 {
+            return Ok(None);
+        }
+
+        let Some(source_address) = raw
+            .payload
+            .get(..DOIP_ROUTING_ACTIVATION_REQ_SRC_LEN)
+            .and_then(|bytes| bytes.try_into().ok())
+        else {
+            return Ok(None);
+        };
+
+        let res = DoipPayload::RoutingActivationResponse(RoutingActivationResponse {
+            logical_address: source_address,
+            source_address: self.shared.config.logical_address.to_bytes(),
+            activation_code: ActivationCode::DeniedUnsupportedRoutingActivationType,
+            buffer: [0; 4],
+        });
+
+        Ok(Some(self.message(res)?))
+    }
+
+    /// Returns the reply to a request which may be received on both TCP and
+    /// UDP.
+    fn respond_udp(&self, payload: DoipPayload) -> Result<Option<DoipFrame>, ServerError> {
+        let config = &self.shared.config;
+
+        let reply = match payload {
+            DoipPayload::VehicleIdentificationRequest(_) => self.announcement(),
+            DoipPayload::VehicleIdentificationRequestEid(req) if req.eid == config.eid => {
+                self.announcement()
+            }
+            DoipPayload::VehicleIdentificationRequestVin(req) if req.vin == config.vin => {
+                self.announcement()
+            }
+            DoipPayload::EntityStatusRequest(_) => {
+                return Ok(Some(DoipCodec::build_entity_status_response(
+                    config.protocol_version,
+                    config.node_type,
+                    config.max_concurrent_sockets,
+                    self.open_sockets(),
+                    config.max_data_size,
+                )?));
+            }
+            DoipPayload::PowerInformationRequest(_) => {
+                DoipPayload::PowerInformationResponse(PowerInformationResponse {
+                    power_mode: config.power_mode,
+                })
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(self.message(reply)?.into()))
+    }
+
+    fn announcement(&self) -> DoipPayload {
+        let config = &self.shared.config;
+
+        DoipPayload::VehicleAnnouncementMessage(VehicleAnnouncementMessage {
+            vin: config.vin,
+            logical_address: config.logical_address.to_bytes(),
+            eid: config.eid,
+            gid: config.gid,
+            further_action: ActionCode::NoFurtherActionRequired,
+            vin_gid_sync: None,
+        })
+    }
+
+    fn message(&self, payload: DoipPayload) -> Result<DoipMessage, ServerError> {
+        Ok(DoipCodec::build_message(
+            self.shared.config.protocol_version,
+            payload,
+        )?)
+    }
+}
+
+impl fmt::Debug for DoipServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoipServer")
+            .field("config", &self.shared.config)
+            .field("targets", &self.shared.targets.keys())
+            .field("open_sockets", &self.shared.open_sockets)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder of a [`DoipServer`], collecting the handlers of its target
+/// addresses before the server is shared between sockets.
+pub struct DoipServerBuilder {
+    config: ServerConfig,
+    activation_handler: Box<dyn RoutingActivationHandler>,
+    targets: HashMap<LogicalAddress, Arc<dyn DiagnosticHandler>>,
+}
+
+impl DoipServerBuilder {
+    /// Registers the handler for diagnostic messages to `target_address`,
+    /// replacing any handler previously registered for it.
+    #[must_use]
+    pub fn with_target<H>(mut self, target_address: LogicalAddress, handler: H) -> Self
+    where
+        H: DiagnosticHandler + 'static,
+    {
+        self.targets.insert(target_address, Arc::new(handler));
+        self
+    }
+
+    /// Builds the server.
+    #[must_use]
+    pub fn build(self) -> DoipServer {
+        DoipServer {
+            shared: Arc::new(Shared {
+                config: self.config,
+                activation_handler: self.activation_handler,
+                targets: self.targets,
+                open_sockets: AtomicU8::new(0),
+                active_sources: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for DoipServerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoipServerBuilder")
+            .field("config", &self.config)
+            .field("targets", &self.targets.keys())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns `true` if ISO 13400-2 requires closing the socket after responding
+/// to a routing activation request with `activation_code`.
+fn closes_socket(activation_code: ActivationCode) -> bool {
+    matches!(
+        activation_code,
+        ActivationCode::DeniedUnknownSourceAddress
+            | ActivationCode::DeniedTCPSocketsFull
+            | ActivationCode::DeniedTCPSocketAlreadyConnected
+            | ActivationCode::DeniedSourceIsAlreadyActive
+            | ActivationCode::DeniedUnsupportedRoutingActivationType
+    )
+}
+
+/// Signals between the socket on which a source address is active and the
+/// sockets requesting routing activation for the same address.
+#[derive(Default)]
+struct AliveCheck {
+    /// Asks the socket to send an alive check request to its tester.
+    requested: Notify,
+    /// Signalled once the tester answered an alive check request.
+    answered: Notify,
+    /// Tells the socket another socket took over its source address.
+    replaced: Notify,
+}
+
+impl AliveCheck {
+    /// Asks the tester whether it is still alive, `true` if it answered within
+    /// `alive_check_timeout`.
+    async fn check(&self, alive_check_timeout: Duration) -> bool {
+        let mut answered = pin!(self.answered.notified());
+        answered.as_mut().enable();
+        self.requested.notify_one();
+
+        timeout(alive_check_timeout, answered).await.is_ok()
+    }
+}
+
+/// Per-socket state, released when the socket closes.
+struct Socket {
+    shared: Arc<Shared>,
+    active_source: Option<LogicalAddress>,
+    alive_check: Arc<AliveCheck>,
+    encrypted: bool,
+}
+
+impl Socket {
+    /// Counts the socket as open, `None` if the count would overflow.
+    fn open(shared: Arc<Shared>, encrypted: bool) -> Option<Self> {
+        shared
+            .open_sockets
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |open| {
+                open.checked_add(1)
+            })
+            .ok()?;
+
+        Some(Socket {
+            shared,
+            active_source: None,
+            alive_check: Arc::default(),
+            encrypted,
+        })
+    }
+
+    /// Activates routing for the source address of the request.
+    ///
+    /// If the address is already active on another socket, that socket's
+    /// tester is sent an alive check request. Routing is only activated on this
+    /// socket, closing the other one, if the request goes unanswered.
+    async fn activate(&mut self, req: &RoutingActivationRequest) -> ActivationCode {
+        let source_address = LogicalAddress::from(req.source_address);
+
+        match self.active_source {
+            Some(active) if active == source_address => {
+                return ActivationCode::SuccessfullyActivated
+            }
+            Some(_) => return ActivationCode::DeniedTCPSocketAlreadyConnected,
+            None => {}
+        }
+
+        if self.shared.config.tls_required && !self.encrypted {
+            return ActivationCode::DeniedRequestEncryptedTLSConnection;
+        }
+
+        if self.shared.open_sockets.load(Ordering::Relaxed)
+            > self.shared.config.max_concurrent_sockets
+        {
+            return ActivationCode::DeniedTCPSocketsFull;
+        }
+
+        let holder = self.shared.active_sources().get(&source_address).cloned();
+        if let Some(holder) = &holder {
+            if holder.check(self.shared.config.alive_check_timeout).await {
+                return ActivationCode::DeniedSourceIsAlreadyActive;
+            }
+        }
+
+        let mut active_sources = self.shared.active_sources();
+
+        // Another socket may have activated the address during the alive check
+        let taken = match (active_sources.get(&source_address), &holder) {
+            (Some(current), Some(holder)) => !Arc::ptr_eq(current, holder),
+            (current, _) => current.is_some(),
+        };
+        if taken {
+            return ActivationCode::DeniedSourceIsAlreadyActive;
+        }
+
+        let activation_code = self
+            .shared
+            .activation_handler
+            .activate(source_address, req.activation_type);
+
+        if activation_code == ActivationCode::SuccessfullyActivated {
+            if let Some(holder) =
+                active_sources.insert(source_address, Arc::clone(&self.alive_check))
+            {
+                holder.replaced.notify_one();
+            }
+            self.active_source = Some(source_address);
+        }
+
+        activation_code
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some(source_address) = self.active_source {
+            let mut active_sources = self.shared.active_sources();
+
+            // The address may have been taken over by another socket
+            if active_sources
+                .get(&source_address)
+                .is_some_and(|active| Arc::ptr_eq(active, &self.alive_check))
+            {
+                active_sources.remove(&source_address);
+            }
+        }
+
+        // Never fails, the socket has been counted when it was opened
+        let _ =
+            self.shared
+                .open_sockets
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |open| {
+                    open.checked_sub(1)
+                });
+    }
+}
+
+/// What a socket waiting for its tester has been woken up by.
+enum Wakeup {
+    /// A frame has been read, or the connection closed or failed.
+    Read(Result<Option<DoipFrame>, DecodeError>),
+    /// The tester has been inactive for too long.
+    Inactive,
+    /// Another socket requests an alive check of the tester.
+    AliveCheck,
+    /// Another socket took over the source address of the tester.
+    Replaced,
+}
+
+/// A stream together with its codec and buffers.
+struct Connection<S> {
+    io: S,
+    codec: DoipCodec,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    /// Reads the next frame, `None` once the tester closed the connection.
+    async fn read(&mut self) -> Result<Option<DoipFrame>, DecodeError> {
+        loop {
+            if let Some(frame) =
+                tokio_util::codec::Decoder::decode(&mut self.codec, &mut self.read_buf)?
+            {
+                return Ok(Some(frame));
+            }
+
+            if self.io.read_buf(&mut self.read_buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Waits for the next frame until `deadline`, unless another socket
+    /// signals `alive_check` first.
+    async fn wait(&mut self, deadline: Instant, alive_check: &AliveCheck) -> Wakeup {
+        let mut read = pin!(timeout_at(deadline, self.read()));
+        let mut requested = pin!(alive_check.requested.notified());
+        let mut replaced = pin!(alive_check.replaced.notified());
+
+        poll_fn(|cx| {
+            if replaced.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Wakeup::Replaced);
+            }
+            if requested.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Wakeup::AliveCheck);
+            }
+
+            read.as_mut()
+                .poll(cx)
+                .map(|frame| frame.map_or(Wakeup::Inactive, Wakeup::Read))
+        })
+        .await
+    }
+
+    async fn send(&mut self, frame: impl Into<DoipFrame>) -> Result<(), ServerError> {
+        tokio_util::codec::Encoder::encode(&mut self.codec, frame.into(), &mut self.write_buf)?;
+        self.io.write_all_buf(&mut self.write_buf).await?;
+        self.io.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use doip_definitions::{
+        message::DoipMessage,
+        payload::{
+            ActivationCode, ActivationType, DiagnosticNackCode, DoipPayload, EntityStatusRequest,
+            NodeType, RoutingActivationRequest, RoutingActivationResponse,
+        },
+    };
+    use futures::{SinkExt, StreamExt};
+    use tokio::{
+        net::{TcpListener, TcpStream, UdpSocket},
+        sync::{mpsc, oneshot},
+    };
+    use tokio_util::codec::Framed;
+
+    use crate::{
+        discover,
+        test_util::{extended, message},
+        ClientConfig, ClientError, DiscoveryConfig, DoipClient, DoipCodec, DoipFrame, DoipServer,
+        LogicalAddress, PayloadExtension, ServerConfig,
+    };
+
+    use super::Socket;
+
+    const ENTITY: LogicalAddress = LogicalAddress::new(0x1000);
+    const ECU: LogicalAddress = LogicalAddress::new(0x1001);
+
+    fn server() -> DoipServer {
+        let config = ServerConfig::new(ENTITY)
+            .with_max_concurrent_sockets(2)
+            .with_identification(*b"WVWZZZ1JZXW000001", [0, 0, 0, 0, 0, 1], [0; 6]);
+
+        DoipServer::builder(config, |source_address: LogicalAddress, _| {
+            if source_address == LogicalAddress::new(0x0e80) {
+                ActivationCode::SuccessfullyActivated
+            } else {
+                ActivationCode::DeniedUnknownSourceAddress
+            }
+        })
+        .with_target(ECU, |_, mut data: Vec<u8>| async move {
+            data[0] |= 0x40;
+            Some(data)
+        })
+        .build()
+    }
+
+    async fn spawn_server(server: DoipServer) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve(listener).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_server_diagnostic_request() {
+        let addr = spawn_server(server()).await;
+
+        let mut client = DoipClient::connect(addr, ClientConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(client.entity_address(), ENTITY);
+
+        let res = client.diagnostic_request(ECU, &[0x3e, 0x00]).await.unwrap();
+        assert_eq!(res, [0x7e, 0x00]);
+
+        let res = client
+            .send_diagnostic(LogicalAddress::new(0x2000), &[0x3e, 0x00])
+            .await;
+        assert!(matches!(
+            res,
+            Err(ClientError::DiagnosticNack(
+                DiagnosticNackCode::UnknownTargetAddress
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_gateway() {
+        // ECU on a sub-network, responding later than A_DoIP_Diagnostic_Message
+        let (tx, mut rx) = mpsc::channel::<(Vec<u8>, oneshot::Sender<Vec<u8>>)>(1);
+        tokio::spawn(async move {
+            while let Some((req, reply)) = rx.recv().await {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                let _ = reply.send(vec![req[0] | 0x40, req[1]]);
+            }
+        });
+
+        let server = DoipServer::builder(ServerConfig::new(ENTITY), |_, _| {
+            ActivationCode::SuccessfullyActivated
+        })
+        .with_target(ECU, move |_, data| {
+            let tx = tx.clone();
+            async move {
+                let (reply, res) = oneshot::channel();
+                tx.send((data, reply)).await.ok()?;
+                res.await.ok()
+            }
+        })
+        .build();
+        let addr = spawn_server(server).await;
+
+        let config =
+            ClientConfig::default().with_diagnostic_message_timeout(Duration::from_millis(100));
+        let mut client = DoipClient::connect(addr, config).await.unwrap();
+
+        let res = client.diagnostic_request(ECU, &[0x22, 0xf1]).await.unwrap();
+        assert_eq!(res, [0x62, 0xf1]);
+    }
+
+    #[tokio::test]
+    async fn test_server_routing_activation() {
+        let addr = spawn_server(server()).await;
+
+        let config = ClientConfig::new(LogicalAddress::new(0x0e81));
+        let res = DoipClient::connect(addr, config).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::RoutingActivationDenied(
+                ActivationCode::DeniedUnknownSourceAddress
+            ))
+        ));
+
+        let _client = DoipClient::connect(addr, ClientConfig::default())
+            .await
+            .unwrap();
+        let res = DoipClient::connect(addr, ClientConfig::default()).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::RoutingActivationDenied(
+                ActivationCode::DeniedSourceIsAlreadyActive
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_routing_activation_oem_specific() {
+        let addr = spawn_server(server()).await;
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut tester = Framed::new(stream, DoipCodec::new());
+        let req = extended(
+            DoipPayload::RoutingActivationRequest(RoutingActivationRequest {
+                source_address: [0x0e, 0x80],
+                activation_type: ActivationType::Default,
+                buffer: [0; 4],
+            }),
+            PayloadExtension::OemSpecific([0xde, 0xad, 0xbe, 0xef]),
+        );
+        tester.send(req).await.unwrap();
+
+        let Some(Ok(DoipFrame::Message(res))) = tester.next().await else {
+            panic!("Expected a routing activation response");
+        };
+        let DoipPayload::RoutingActivationResponse(res) = res.payload else {
+            panic!("Expected a routing activation response");
+        };
+        assert_eq!(res.activation_code, ActivationCode::SuccessfullyActivated);
+    }
+
+    #[tokio::test]
+    async fn test_server_reserved_activation_type() {
+        let addr = spawn_server(server()).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(
+            &mut stream,
+            &[
+                0x02, 0xfd, 0x00, 0x05, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x80, 0x7f, 0x00, 0x00, 0x00,
+                0x00,
+            ],
+        )
+        .await
+        .unwrap();
+
+        let mut tester = Framed::new(stream, DoipCodec::new());
+        let res = denied(&mut tester).await;
+        assert_eq!(res.logical_address, [0x0e, 0x80]);
+        assert_eq!(
+            res.activation_code,
+            ActivationCode::DeniedUnsupportedRoutingActivationType
+        );
+    }
+
+    fn activation_request(source_address: [u8; 2]) -> DoipMessage {
+        message(DoipPayload::RoutingActivationRequest(
+            RoutingActivationRequest {
+                source_address,
+                activation_type: ActivationType::Default,
+                buffer: [0; 4],
+            },
+        ))
+    }
+
+    /// Reads the routing activation response of the entity, expecting it to
+    /// close the socket afterwards.
+    async fn denied(tester: &mut Framed<TcpStream, DoipCodec>) -> RoutingActivationResponse {
+        let Some(Ok(DoipFrame::Message(res))) = tester.next().await else {
+            panic!("Expected a routing activation response");
+        };
+        let DoipPayload::RoutingActivationResponse(res) = res.payload else {
+            panic!("Expected a routing activation response");
+        };
+        assert!(tester.next().await.is_none());
+
+        res
+    }
+
+    #[tokio::test]
+    async fn test_server_denial_closes_socket() {
+        let addr = spawn_server(server()).await;
+        let connect = || async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            Framed::new(stream, DoipCodec::new())
+        };
+
+        let mut tester = connect().await;
+        tester.send(activation_request([0x0e, 0x81])).await.unwrap();
+        let res = denied(&mut tester).await;
+        assert_eq!(
+            res.activation_code,
+            ActivationCode::DeniedUnknownSourceAddress
+        );
+
+        let mut tester = connect().await;
+        tester.send(activation_request([0x0e, 0x80])).await.unwrap();
+        assert!(tester.next().await.is_some());
+        tester.send(activation_request([0x0e, 0x81])).await.unwrap();
+        let res = denied(&mut tester).await;
+        assert_eq!(
+            res.activation_code,
+            ActivationCode::DeniedTCPSocketAlreadyConnected
+        );
+
+        let _client = DoipClient::connect(addr, ClientConfig::default())
+            .await
+            .unwrap();
+        let mut tester = connect().await;
+        tester.send(activation_request([0x0e, 0x80])).await.unwrap();
+        let res = denied(&mut tester).await;
+        assert_eq!(
+            res.activation_code,
+            ActivationCode::DeniedSourceIsAlreadyActive
+        );
+
+        let _idle = connect().await;
+        let mut tester = connect().await;
+        tester.send(activation_request([0x0e, 0x80])).await.unwrap();
+        let res = denied(&mut tester).await;
+        assert_eq!(res.activation_code, ActivationCode::DeniedTCPSocketsFull);
+    }
+
+    #[tokio::test]
+    async fn test_server_alive_check_unanswered() {
+        let config = ServerConfig::new(ENTITY)
+            .with_max_concurrent_sockets(2)
+            .with_alive_check_timeout(Duration::from_millis(100));
+        let server = DoipServer::new(config, |_, _| ActivationCode::SuccessfullyActivated);
+        let addr = spawn_server(server).await;
+
+        // Crashed tester, no longer answering alive check requests
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut crashed = Framed::new(stream, DoipCodec::new());
+        crashed
+            .send(activation_request([0x0e, 0x80]))
+            .await
+            .unwrap();
+        assert!(crashed.next().await.is_some());
+
+        let client = DoipClient::connect(addr, ClientConfig::default()).await;
+        assert!(client.is_ok());
+
+        let Some(Ok(DoipFrame::Message(req))) = crashed.next().await else {
+            panic!("Expected an alive check request");
+        };
+        assert!(matches!(req.payload, DoipPayload::AliveCheckRequest(_)));
+        assert!(crashed.next().await.is_none());
+    }
+
+    #[test]
+    fn test_server_socket_count_saturates() {
+        let server = server();
+        server.shared.open_sockets.store(u8::MAX, Ordering::Relaxed);
+
+        assert!(Socket::open(Arc::clone(&server.shared), false).is_none());
+        assert_eq!(server.open_sockets(), u8::MAX);
+
+        server.shared.open_sockets.store(0, Ordering::Relaxed);
+        let socket = Socket::open(Arc::clone(&server.shared), false);
+        assert_eq!(server.open_sockets(), 1);
+
+        drop(socket);
+        assert_eq!(server.open_sockets(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_server_initial_inactivity() {
+        let config = ServerConfig::new(ENTITY)
+            .with_initial_inactivity_timeout(Duration::from_millis(50))
+            .with_general_inactivity_timeout(Duration::from_millis(100));
+        let server = DoipServer::new(config, |_, _| ActivationCode::SuccessfullyActivated);
+
+        let addr = spawn_server(server).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0; 8];
+        let read = tokio::time::timeout(
+            Duration::from_secs(1),
+            tokio::io::AsyncReadExt::read(&mut stream, &mut buf),
+        )
+        .await;
+        assert!(matches!(read, Ok(Ok(0))));
+
+        let client = DoipClient::connect(addr, ClientConfig::default()).await;
+        assert!(client.is_ok());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut client = client.unwrap();
+        let res = client.send_diagnostic(ECU, &[0x3e, 0x00]).await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_entity_status() {
+        let addr = spawn_server(server()).await;
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut tester = Framed::new(stream, DoipCodec::new());
+        let req = message(DoipPayload::EntityStatusRequest(EntityStatusRequest {}));
+        tester.send(req).await.unwrap();
+
+        let Some(Ok(DoipFrame::Message(res))) = tester.next().await else {
+            panic!("Expected an entity status response");
+        };
+        let DoipPayload::EntityStatusResponse(res) = res.payload else {
+            panic!("Expected an entity status response");
+        };
+        assert_eq!(res.node_type, NodeType::DoipNode);
+        assert_eq!(res.max_concurrent_sockets, [2]);
+        assert_eq!(res.currently_open_sockets, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_server_generic_nack() {
+        let addr = spawn_server(server()).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(
+            &mut stream,
+            &[0x02, 0xfd, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00],
+        )
+        .await
+        .unwrap();
+
+        let mut tester = Framed::new(stream, DoipCodec::new());
+        let Some(Ok(DoipFrame::Message(res))) = tester.next().await else {
+            panic!("Expected a generic nack");
+        };
+        assert!(matches!(res.payload, DoipPayload::GenericNack(_)));
+    }
+
+    #[tokio::test]
+    async fn test_server_udp_discovery() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = DiscoveryConfig::new()
+            .with_bind_address("127.0.0.1:0".parse().unwrap())
+            .with_target(socket.local_addr().unwrap())
+            .with_window(Duration::from_millis(100));
+
+        let server = server();
+        tokio::spawn(async move { server.serve_udp(socket).await });
+
+        let entities = discover(&config).await.unwrap();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].logical_address, ENTITY);
+        assert_eq!(entities[0].eid, [0, 0, 0, 0, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_server_activation_type() {
+        let server = DoipServer::new(
+            ServerConfig::new(ENTITY),
+            |_, activation_type: ActivationType| {
+                if activation_type == ActivationType::Default {
+                    ActivationCode::SuccessfullyActivated
+                } else {
+                    ActivationCode::DeniedUnsupportedRoutingActivationType
+                }
+            },
+        );
+
+        let addr = spawn_server(server).await;
+
+        let config = ClientConfig::default().with_activation_type(ActivationType::WwhObd);
+        let res = DoipClient::connect(addr, config).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::RoutingActivationDenied(
+                ActivationCode::DeniedUnsupportedRoutingActivationType
+            ))
+        ));
+    }
+}
@@ -0,0 +1,26 @@
+//! Fixtures shared between the tests of the crate.
+
+use doip_definitions::{header::ProtocolVersion, message::DoipMessage, payload::DoipPayload};
+
+use crate::{DoipCodec, ExtendedMessage, PayloadExtension};
+
+/// Builds an ISO 13400-2:2012 message, deriving the header from the payload.
+pub(crate) fn message(payload: DoipPayload) -> DoipMessage {
+    DoipCodec::build_message(ProtocolVersion::Iso13400_2012, payload).unwrap()
+}
+
+/// Builds a message announcing `payload_length`, which need not match the
+/// payload.
+pub(crate) fn with_length(payload: DoipPayload, payload_length: u32) -> DoipMessage {
+    let mut message = message(payload);
+    message.header.payload_length = payload_length;
+    message
+}
+
+/// Adds optional fields to a message, adjusting the announced length.
+pub(crate) fn extended(payload: DoipPayload, extension: PayloadExtension) -> ExtendedMessage {
+    let mut message = message(payload);
+    message.header.payload_length += u32::try_from(extension.encoded_len()).unwrap();
+
+    ExtendedMessage { message, extension }
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use tokio::{
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::timeout,
+};
+use tokio_rustls::{rustls::pki_types::ServerName, TlsAcceptor, TlsConnector};
+
+pub use tokio_rustls::rustls;
+
+use crate::{ClientConfig, ClientError, DoipClient, DoipServer};
+
+/// TCP port of `DoIP` entities accepting TLS connections.
+pub const DOIP_TLS_PORT: u16 = 3496;
+
+/// TLS settings of a [`DoipClient`].
+#[derive(Debug, Clone)]
+pub struct TlsClientConfig {
+    config: Arc<rustls::ClientConfig>,
+    server_name: ServerName<'static>,
+    port: u16,
+}
+
+impl TlsClientConfig {
+    /// Creates TLS settings verifying the entity against `server_name` and
+    /// connecting to port 3496.
+    #[must_use]
+    pub fn new(config: Arc<rustls::ClientConfig>, server_name: ServerName<'static>) -> Self {
+        TlsClientConfig {
+            config,
+            server_name,
+            port: DOIP_TLS_PORT,
+        }
+    }
+
+    /// Sets the port reconnected to when the entity requests an encrypted
+    /// connection.
+    #[must_use]
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Returns the rustls configuration.
+    #[must_use]
+    pub fn config(&self) -> &Arc<rustls::ClientConfig> {
+        &self.config
+    }
+
+    /// Returns the name the certificate of the entity is verified against.
+    #[must_use]
+    pub fn server_name(&self) -> &ServerName<'static> {
+        &self.server_name
+    }
+
+    /// Returns the port reconnected to when the entity requests an encrypted
+    /// connection.
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl DoipClient {
+    /// Connects to a `DoIP` entity over TLS and activates routing.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClientError::IOError` if the TLS handshake failed, otherwise
+    /// see [`DoipClient::connect`].
+    pub async fn connect_tls<A: ToSocketAddrs>(
+        addr: A,
+        tls: &TlsClientConfig,
+        config: ClientConfig,
+    ) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+
+        let stream = TlsConnector::from(Arc::clone(&tls.config))
+            .connect(tls.server_name.clone(), stream)
+            .await?;
+
+        Self::from_stream(stream, config).await
+    }
+}
+
+impl DoipServer {
+    /// Accepts TLS connections until the listener fails, serving each on its
+    /// own task. Connections which cannot be set up, fail the handshake or do
+    /// not complete it within the initial inactivity timeout are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the listener.
+    pub async fn serve_tls(
+        &self,
+        listener: TcpListener,
+        tls: Arc<rustls::ServerConfig>,
+    ) -> std::io::Result<()> {
+        let acceptor = TlsAcceptor::from(tls);
+        let handshake_timeout = self.config().initial_inactivity_timeout();
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            if stream.set_nodelay(true).is_err() {
+                continue;
+            }
+
+            let server = self.clone();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let stream = timeout(handshake_timeout, acceptor.accept(stream))
+                    .await
+                    .map_err(std::io::Error::from)??;
+
+                server.serve_connection(stream, true).await
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+    use doip_definitions::payload::ActivationCode;
+    use tokio::{
+        io::AsyncReadExt,
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName};
+
+    use crate::{
+        rustls, ClientConfig, ClientError, DoipClient, DoipServer, LogicalAddress, ServerConfig,
+        TlsClientConfig,
+    };
+
+    const ECU: LogicalAddress = LogicalAddress::new(0x1001);
+
+    /// Self-signed certificate for `localhost`, valid from 2020 to 2119.
+    static CERT: &[u8] = include_bytes!("../tests/fixtures/localhost.cert.der");
+
+    /// PKCS #8 encoded P-256 key of [`CERT`].
+    static KEY: &[u8] = include_bytes!("../tests/fixtures/localhost.key.der");
+
+    fn tls_configs() -> (Arc<rustls::ServerConfig>, Arc<rustls::ClientConfig>) {
+        let cert = CertificateDer::from(CERT);
+        let key = PrivatePkcs8KeyDer::from(KEY);
+
+        let server = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], key.into())
+            .unwrap();
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let client = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        (Arc::new(server), Arc::new(client))
+    }
+
+    /// Serves a server requiring TLS on a plain and a TLS port.
+    async fn spawn_server(tls: Arc<rustls::ServerConfig>) -> (SocketAddr, SocketAddr) {
+        let config = ServerConfig::new(LogicalAddress::new(0x1000))
+            .with_max_concurrent_sockets(2)
+            .with_initial_inactivity_timeout(Duration::from_millis(100))
+            .with_tls_required(true);
+        let server = DoipServer::builder(config, |_, _| ActivationCode::SuccessfullyActivated)
+            .with_target(ECU, |_, _| async { Some(vec![0x7e, 0x00]) })
+            .build();
+
+        let plain = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let encrypted = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addrs = (plain.local_addr().unwrap(), encrypted.local_addr().unwrap());
+
+        let plain_server = server.clone();
+        tokio::spawn(async move { plain_server.serve(plain).await });
+        tokio::spawn(async move { server.serve_tls(encrypted, tls).await });
+
+        addrs
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnects_over_tls() {
+        let (server_tls, client_tls) = tls_configs();
+
+        let (plain, encrypted) = spawn_server(server_tls).await;
+
+        let tls = TlsClientConfig::new(client_tls, ServerName::try_from("localhost").unwrap())
+            .with_port(encrypted.port());
+        let config = ClientConfig::default().with_tls(tls);
+
+        let mut client = DoipClient::connect(plain, config).await.unwrap();
+        let res = client.diagnostic_request(ECU, &[0x3e, 0x00]).await.unwrap();
+        assert_eq!(res, [0x7e, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_client_without_tls_denied() {
+        let (server_tls, client_tls) = tls_configs();
+
+        let (plain, encrypted) = spawn_server(server_tls).await;
+
+        let res = DoipClient::connect(plain, ClientConfig::default()).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::RoutingActivationDenied(
+                ActivationCode::DeniedRequestEncryptedTLSConnection
+            ))
+        ));
+
+        let tls = TlsClientConfig::new(client_tls, ServerName::try_from("localhost").unwrap());
+        let res = DoipClient::connect_tls(encrypted, &tls, ClientConfig::default()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_handshake_dropped() {
+        let (server_tls, client_tls) = tls_configs();
+
+        let (_, encrypted) = spawn_server(server_tls).await;
+
+        let mut stalled = TcpStream::connect(encrypted).await.unwrap();
+        let mut buf = [0; 8];
+        let read = tokio::time::timeout(Duration::from_secs(1), stalled.read(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0))));
+
+        let tls = TlsClientConfig::new(client_tls, ServerName::try_from("localhost").unwrap());
+        let res = DoipClient::connect_tls(encrypted, &tls, ClientConfig::default()).await;
+        assert!(res.is_ok());
+    }
+}
@@ -0,0 +1,402 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use doip_definitions::{
+    message::DoipMessage,
+    payload::{DoipPayload, NodeType},
+};
+
+use crate::{DoipCodec, DoipFrame, ExtendedMessage, LogicalAddress};
+
+/// Semantic rules of ISO 13400-2 which go beyond the byte layout of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// The logical address of a tester lies outside `0x0E00..=0x0FFF`.
+    TesterAddressRange,
+
+    /// A functional address is used as a source address.
+    FunctionalSourceAddress,
+
+    /// The VIN contains characters which are not ASCII.
+    VinNotAscii,
+
+    /// The VIN/GID synchronisation status is only sent by gateways.
+    VinGidSyncFromGateway,
+
+    /// A diagnostic message carries no user data.
+    EmptyUserData,
+
+    /// More sockets are reported open than are supported concurrently.
+    OpenSocketsExceedMaximum,
+}
+
+impl Rule {
+    /// Returns a short description of the rule.
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::TesterAddressRange => "tester logical address outside 0x0E00..=0x0FFF",
+            Rule::FunctionalSourceAddress => "functional address used as source address",
+            Rule::VinNotAscii => "VIN contains non-ASCII characters",
+            Rule::VinGidSyncFromGateway => "VIN/GID sync status is only sent by gateways",
+            Rule::EmptyUserData => "diagnostic message without user data",
+            Rule::OpenSocketsExceedMaximum => "more sockets open than supported concurrently",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// How severe the violation of a rule is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The rule could not be checked conclusively, e.g. because it depends on
+    /// the node type of the sender.
+    Info,
+
+    /// The message is unusual but may be accepted by a tolerant receiver.
+    Warning,
+
+    /// The message violates a requirement of ISO 13400-2.
+    Error,
+}
+
+/// A rule violated by a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    /// The violated rule.
+    pub rule: Rule,
+
+    /// How severe the violation is.
+    pub severity: Severity,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.severity, self.rule)
+    }
+}
+
+/// Checks messages against the semantic rules of ISO 13400-2.
+///
+/// Validation can be run on both decoded messages and messages about to be
+/// encoded, either by hand or by the codec, see [`DoipCodec::with_validator`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Validator {
+    node_type: Option<NodeType>,
+}
+
+impl Validator {
+    /// Creates a validator which knows nothing about the sender of messages.
+    #[must_use]
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    /// Sets the node type of the sender, e.g. as reported in its entity status
+    /// response, so that rules depending on it are checked conclusively.
+    #[must_use]
+    pub fn with_node_type(mut self, node_type: NodeType) -> Self {
+        self.node_type = Some(node_type);
+        self
+    }
+
+    /// Returns every rule the message violates, an empty list if it conforms.
+    #[must_use]
+    pub fn validate(&self, message: &DoipMessage) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut violate = |rule, severity| violations.push(Violation { rule, severity });
+
+        match &message.payload {
+            DoipPayload::VehicleIdentificationRequestVin(req) => {
+                if !req.vin.is_ascii() {
+                    violate(Rule::VinNotAscii, Severity::Error);
+                }
+            }
+            DoipPayload::VehicleAnnouncementMessage(msg) => {
+                if !msg.vin.is_ascii() {
+                    violate(Rule::VinNotAscii, Severity::Error);
+                }
+
+                if msg.vin_gid_sync.is_some() {
+                    match self.node_type {
+                        Some(NodeType::DoipGateway) => {}
+                        Some(NodeType::DoipNode) => {
+                            violate(Rule::VinGidSyncFromGateway, Severity::Error);
+                        }
+                        None => violate(Rule::VinGidSyncFromGateway, Severity::Info),
+                    }
+                }
+            }
+            DoipPayload::RoutingActivationRequest(req) => {
+                if !LogicalAddress::from(req.source_address).is_tester() {
+                    violate(Rule::TesterAddressRange, Severity::Error);
+                }
+
+                if LogicalAddress::from(req.source_address).is_functional() {
+                    violate(Rule::FunctionalSourceAddress, Severity::Error);
+                }
+            }
+            DoipPayload::RoutingActivationResponse(res) => {
+                if !LogicalAddress::from(res.logical_address).is_tester() {
+                    violate(Rule::TesterAddressRange, Severity::Error);
+                }
+
+                if LogicalAddress::from(res.source_address).is_functional() {
+                    violate(Rule::FunctionalSourceAddress, Severity::Error);
+                }
+            }
+            DoipPayload::AliveCheckResponse(res) => {
+                if !LogicalAddress::from(res.source_address).is_tester() {
+                    violate(Rule::TesterAddressRange, Severity::Error);
+                }
+            }
+            DoipPayload::EntityStatusResponse(res) => {
+                if res.currently_open_sockets[0] > res.max_concurrent_sockets[0] {
+                    violate(Rule::OpenSocketsExceedMaximum, Severity::Warning);
+                }
+            }
+            DoipPayload::DiagnosticMessage(msg) => {
+                if LogicalAddress::from(msg.source_address).is_functional() {
+                    violate(Rule::FunctionalSourceAddress, Severity::Error);
+                }
+
+                if msg.message.is_empty() {
+                    violate(Rule::EmptyUserData, Severity::Warning);
+                }
+            }
+            DoipPayload::DiagnosticMessageAck(ack) => {
+                if LogicalAddress::from(ack.source_address).is_functional() {
+                    violate(Rule::FunctionalSourceAddress, Severity::Error);
+                }
+            }
+            DoipPayload::DiagnosticMessageNack(nack) => {
+                if LogicalAddress::from(nack.source_address).is_functional() {
+                    violate(Rule::FunctionalSourceAddress, Severity::Error);
+                }
+            }
+            DoipPayload::GenericNack(_)
+            | DoipPayload::VehicleIdentificationRequest(_)
+            | DoipPayload::VehicleIdentificationRequestEid(_)
+            | DoipPayload::AliveCheckRequest(_)
+            | DoipPayload::EntityStatusRequest(_)
+            | DoipPayload::PowerInformationRequest(_)
+            | DoipPayload::PowerInformationResponse(_) => {}
+        }
+
+        violations
+    }
+}
+
+/// Opt-in semantic validation of messages, see [`Validator`].
+pub trait Validate {
+    /// Returns every rule the message violates, an empty list if it conforms.
+    fn validate(&self) -> Vec<Violation>;
+}
+
+impl Validate for DoipMessage {
+    fn validate(&self) -> Vec<Violation> {
+        Validator::new().validate(self)
+    }
+}
+
+impl DoipCodec {
+    /// Checks a message with the validator of the codec, if any.
+    pub(crate) fn check(&self, message: &DoipMessage) -> Vec<Violation> {
+        self.validator
+            .map(|validator| validator.validate(message))
+            .unwrap_or_default()
+    }
+
+    /// Records the violations of a decoded frame. Frames not holding a typed
+    /// message are not validated.
+    pub(crate) fn check_decoded(&mut self, frame: Option<&DoipFrame>) {
+        if let Some(
+            DoipFrame::Message(message) | DoipFrame::Extended(ExtendedMessage { message, .. }),
+        ) = frame
+        {
+            let violations = self.check(message);
+            self.violations.extend(violations);
+        }
+    }
+
+    /// Records the violations of a message which has been encoded.
+    pub(crate) fn record_violations(&mut self, violations: Vec<Violation>) {
+        self.violations.extend(violations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use doip_definitions::payload::{
+        ActionCode, ActivationType, DiagnosticMessage, DoipPayload, NodeType,
+        RoutingActivationRequest, SyncStatus, VehicleAnnouncementMessage,
+    };
+
+    use crate::{
+        test_util::message, Decoder, DoipCodec, Encoder, Rule, Severity, Validate, Validator,
+        Violation,
+    };
+
+    fn routing_activation_request(
+        source_address: [u8; 2],
+    ) -> doip_definitions::message::DoipMessage {
+        message(DoipPayload::RoutingActivationRequest(
+            RoutingActivationRequest {
+                source_address,
+                activation_type: ActivationType::Default,
+                buffer: [0; 4],
+            },
+        ))
+    }
+
+    #[test]
+    fn test_validate_conforming() {
+        assert!(routing_activation_request([0x0e, 0x80])
+            .validate()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_tester_address() {
+        assert_eq!(
+            routing_activation_request([0x10, 0x00]).validate(),
+            [Violation {
+                rule: Rule::TesterAddressRange,
+                severity: Severity::Error
+            }]
+        );
+
+        let rules: Vec<_> = routing_activation_request([0xe0, 0x00])
+            .validate()
+            .into_iter()
+            .map(|violation| violation.rule)
+            .collect();
+        assert_eq!(
+            rules,
+            [Rule::TesterAddressRange, Rule::FunctionalSourceAddress]
+        );
+    }
+
+    #[test]
+    fn test_validate_diagnostic_message() {
+        let msg = message(DoipPayload::DiagnosticMessage(DiagnosticMessage {
+            source_address: [0xe4, 0x00],
+            target_address: [0x10, 0x01],
+            message: vec![],
+        }));
+
+        let rules: Vec<_> = msg.validate().into_iter().map(|v| v.rule).collect();
+        assert_eq!(rules, [Rule::FunctionalSourceAddress, Rule::EmptyUserData]);
+    }
+
+    #[test]
+    fn test_validate_vehicle_announcement() {
+        let msg = message(DoipPayload::VehicleAnnouncementMessage(
+            VehicleAnnouncementMessage {
+                vin: [0xff; 17],
+                logical_address: [0x10, 0x00],
+                eid: [0; 6],
+                gid: [0; 6],
+                further_action: ActionCode::NoFurtherActionRequired,
+                vin_gid_sync: Some(SyncStatus::VinGidSynchronized),
+            },
+        ));
+
+        assert_eq!(
+            msg.validate(),
+            [
+                Violation {
+                    rule: Rule::VinNotAscii,
+                    severity: Severity::Error
+                },
+                Violation {
+                    rule: Rule::VinGidSyncFromGateway,
+                    severity: Severity::Info
+                }
+            ]
+        );
+
+        let violations = Validator::new()
+            .with_node_type(NodeType::DoipNode)
+            .validate(&msg);
+        assert_eq!(violations[1].severity, Severity::Error);
+
+        let violations = Validator::new()
+            .with_node_type(NodeType::DoipGateway)
+            .validate(&msg);
+        assert_eq!(violations.len(), 1);
+    }
+
+    #[test]
+    fn test_codec_validator_encode() {
+        let mut codec = DoipCodec::new().with_validator(Validator::new());
+        let mut dst = Vec::new();
+
+        codec
+            .to_bytes(routing_activation_request([0x0e, 0x80]), &mut dst)
+            .unwrap();
+        assert!(codec.violations().is_empty());
+
+        codec
+            .to_bytes(routing_activation_request([0x10, 0x00]), &mut dst)
+            .unwrap();
+        assert_eq!(
+            codec.take_violations(),
+            [Violation {
+                rule: Rule::TesterAddressRange,
+                severity: Severity::Error
+            }]
+        );
+        assert!(codec.violations().is_empty());
+    }
+
+    #[test]
+    fn test_codec_validator_decode() {
+        let mut bytes = Vec::new();
+        DoipCodec::new()
+            .to_bytes(routing_activation_request([0x10, 0x00]), &mut bytes)
+            .unwrap();
+
+        let mut codec = DoipCodec::new();
+        assert!(codec.decode_from_bytes(&bytes).unwrap().is_some());
+        assert!(codec.violations().is_empty());
+
+        let mut codec = DoipCodec::new().with_validator(Validator::new());
+        assert!(codec.decode_from_bytes(&bytes).unwrap().is_some());
+        let rules: Vec<_> = codec
+            .take_violations()
+            .into_iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, [Rule::TesterAddressRange]);
+    }
+
+    #[test]
+    fn test_codec_validator_stream() {
+        let mut bytes = bytes::BytesMut::new();
+        let mut codec = DoipCodec::new().with_validator(Validator::new());
+        tokio_util::codec::Encoder::encode(
+            &mut codec,
+            routing_activation_request([0xe0, 0x00]),
+            &mut bytes,
+        )
+        .unwrap();
+        assert_eq!(codec.take_violations().len(), 2);
+
+        tokio_util::codec::Decoder::decode(&mut codec, &mut bytes).unwrap();
+        let rules: Vec<_> = codec
+            .take_violations()
+            .into_iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(
+            rules,
+            [Rule::TesterAddressRange, Rule::FunctionalSourceAddress]
+        );
+    }
+}
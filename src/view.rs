@@ -0,0 +1,376 @@
+use bytes::{Bytes, BytesMut};
+use doip_definitions::{
+    definitions::{
+        DOIP_DIAG_COMMON_SOURCE_LEN, DOIP_DIAG_COMMON_TARGET_LEN, DOIP_HEADER_LEN,
+        DOIP_INV_VERSION_OFFSET, DOIP_TYPE_OFFSET, DOIP_VERSION_OFFSET,
+    },
+    header::{PayloadType, ProtocolVersion},
+};
+
+use crate::{
+    decoder::frame_length, doip_message::header::validate_protocol, DecodeError, Decoder,
+    DoipCodec, DoipFrame, FromBytes,
+};
+
+/// Borrowed view over exactly one encoded `DoIP` message.
+///
+/// Only the framing is checked when the view is created, all other fields are
+/// parsed on access and no payload bytes are copied. The view can be backed by
+/// a plain slice or by a reference counted `Bytes` buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoipMessageView<B> {
+    bytes: B,
+}
+
+impl<B: AsRef<[u8]>> DoipMessageView<B> {
+    /// Creates a view over a buffer holding exactly one message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecodeError::TooShort` if the buffer does not hold the full
+    /// header and payload, `DecodeError::ExceededLength` if it holds additional
+    /// bytes, `DecodeError::FailedProtocolValidation` if the protocol version
+    /// does not match its inverse and `DecodeError::MessageTooLarge` if the
+    /// announced length cannot be addressed.
+    pub fn new(bytes: B) -> Result<Self, DecodeError> {
+        let src = bytes.as_ref();
+
+        if src.len() < DOIP_HEADER_LEN {
+            return Err(DecodeError::TooShort);
+        }
+
+        validate_protocol(src[DOIP_VERSION_OFFSET], src[DOIP_INV_VERSION_OFFSET])
+            .ok_or(DecodeError::FailedProtocolValidation)?;
+
+        match src.len().cmp(&frame_length(src)?) {
+            core::cmp::Ordering::Less => Err(DecodeError::TooShort),
+            core::cmp::Ordering::Greater => Err(DecodeError::ExceededLength),
+            core::cmp::Ordering::Equal => Ok(DoipMessageView { bytes }),
+        }
+    }
+
+    /// Returns the protocol version of the message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecodeError::InvalidProtocolVersion` if the version is unknown.
+    pub fn protocol_version(&self) -> Result<ProtocolVersion, DecodeError> {
+        ProtocolVersion::from_bytes(&self.as_bytes()[DOIP_VERSION_OFFSET..])
+            .ok_or(DecodeError::InvalidProtocolVersion)
+    }
+
+    /// Returns the inverse protocol version of the message.
+    #[must_use]
+    pub fn inverse_protocol_version(&self) -> u8 {
+        self.as_bytes()[DOIP_INV_VERSION_OFFSET]
+    }
+
+    /// Returns the raw payload type code of the message.
+    #[must_use]
+    pub fn payload_type_code(&self) -> u16 {
+        let bytes = self.as_bytes();
+
+        u16::from_be_bytes([bytes[DOIP_TYPE_OFFSET], bytes[DOIP_TYPE_OFFSET + 1]])
+    }
+
+    /// Returns the payload type of the message, if it is defined by ISO 13400-2.
+    #[must_use]
+    pub fn payload_type(&self) -> Option<PayloadType> {
+        PayloadType::from_bytes(&self.as_bytes()[DOIP_TYPE_OFFSET..])
+    }
+
+    /// Returns the payload length announced in the header.
+    #[must_use]
+    pub fn payload_length(&self) -> usize {
+        self.payload().len()
+    }
+
+    /// Returns the payload bytes of the message.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.as_bytes()[DOIP_HEADER_LEN..]
+    }
+
+    /// Returns the complete message, header included.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// Returns a view over the diagnostic message payload, if this message is a
+    /// diagnostic message.
+    #[must_use]
+    pub fn diagnostic_message(&self) -> Option<DiagnosticMessageView<&[u8]>> {
+        if self.payload_type() != Some(PayloadType::DiagnosticMessage) {
+            return None;
+        }
+
+        DiagnosticMessageView::new(self.payload()).ok()
+    }
+
+    /// Decodes the message into its owned representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DecodeError` if the message is malformed.
+    pub fn decode(&self, codec: &mut DoipCodec) -> Result<DoipFrame, DecodeError> {
+        codec
+            .decode_from_bytes(self.as_bytes())?
+            .ok_or(DecodeError::TooShort)
+    }
+
+    /// Returns the underlying buffer.
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+}
+
+impl DoipMessageView<Bytes> {
+    /// Returns the payload bytes of the message without copying them.
+    #[must_use]
+    pub fn payload_bytes(&self) -> Bytes {
+        self.bytes.slice(DOIP_HEADER_LEN..)
+    }
+
+    /// Returns a view over the diagnostic message payload which shares the
+    /// underlying buffer, if this message is a diagnostic message.
+    #[must_use]
+    pub fn diagnostic_message_bytes(&self) -> Option<DiagnosticMessageView<Bytes>> {
+        if self.payload_type() != Some(PayloadType::DiagnosticMessage) {
+            return None;
+        }
+
+        DiagnosticMessageView::new(self.payload_bytes()).ok()
+    }
+}
+
+/// Borrowed view over the payload of a diagnostic message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosticMessageView<B> {
+    payload: B,
+}
+
+impl<B: AsRef<[u8]>> DiagnosticMessageView<B> {
+    const USER_DATA_OFFSET: usize = DOIP_DIAG_COMMON_SOURCE_LEN + DOIP_DIAG_COMMON_TARGET_LEN;
+
+    /// Creates a view over the payload of a diagnostic message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecodeError::TooShort` if the payload does not hold both
+    /// addresses.
+    pub fn new(payload: B) -> Result<Self, DecodeError> {
+        if payload.as_ref().len() < Self::USER_DATA_OFFSET {
+            return Err(DecodeError::TooShort);
+        }
+
+        Ok(DiagnosticMessageView { payload })
+    }
+
+    /// Returns the logical address of the sender.
+    #[must_use]
+    pub fn source_address(&self) -> [u8; 2] {
+        let payload = self.payload.as_ref();
+
+        [payload[0], payload[1]]
+    }
+
+    /// Returns the logical address of the receiver.
+    #[must_use]
+    pub fn target_address(&self) -> [u8; 2] {
+        let payload = self.payload.as_ref();
+
+        [
+            payload[DOIP_DIAG_COMMON_SOURCE_LEN],
+            payload[DOIP_DIAG_COMMON_SOURCE_LEN + 1],
+        ]
+    }
+
+    /// Returns the diagnostic user data, e.g. the UDS request or response.
+    #[must_use]
+    pub fn user_data(&self) -> &[u8] {
+        &self.payload.as_ref()[Self::USER_DATA_OFFSET..]
+    }
+}
+
+impl DiagnosticMessageView<Bytes> {
+    /// Returns the diagnostic user data without copying it.
+    #[must_use]
+    pub fn user_data_bytes(&self) -> Bytes {
+        self.payload.slice(Self::USER_DATA_OFFSET..)
+    }
+}
+
+impl DoipCodec {
+    /// Splits the next complete message off the read buffer without parsing or
+    /// copying its payload.
+    ///
+    /// The header is checked and the recovery policy applied just as for the
+    /// owned decoder, payload contents are not validated.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `DecodeError` if the header is malformed or the message exceeds
+    /// the configured limits.
+    pub fn decode_view(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<DoipMessageView<Bytes>>, DecodeError> {
+        if self.discard_remaining > 0 {
+            self.discard(src);
+
+            if self.discard_remaining > 0 {
+                return Ok(None);
+            }
+        }
+
+        if src.len() < DOIP_HEADER_LEN {
+            return Ok(None);
+        }
+
+        match self.validate_header(src).map_err(DecodeError::from) {
+            Ok(()) => {}
+            Err(err @ (DecodeError::MessageTooLarge | DecodeError::OutOfMemory)) => {
+                self.skip_frame(src);
+                return Err(err);
+            }
+            Err(err) => {
+                self.recover(src, &err);
+                return Err(err);
+            }
+        }
+
+        let frame_length = match frame_length(src) {
+            Ok(frame_length) => frame_length,
+            Err(err) => {
+                self.skip_frame(src);
+                return Err(err);
+            }
+        };
+
+        if src.len() < frame_length {
+            return Ok(None);
+        }
+
+        let bytes = src.split_to(frame_length).freeze();
+
+        DoipMessageView::new(bytes).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use doip_definitions::header::{PayloadType, ProtocolVersion};
+
+    use crate::{
+        DecodeError, DiagnosticMessageView, DoipCodec, DoipFrame, DoipMessageView, RecoveryPolicy,
+    };
+
+    static DIAGNOSTIC_MESSAGE: [u8; 15] = [
+        0x02, 0xfd, 0x80, 0x01, 0x00, 0x00, 0x00, 0x07, 0x0e, 0x00, 0x10, 0x01, 0x22, 0xf1, 0x90,
+    ];
+
+    #[test]
+    fn test_message_view_fields() {
+        let view = DoipMessageView::new(&DIAGNOSTIC_MESSAGE[..]).unwrap();
+
+        assert_eq!(
+            view.protocol_version().unwrap(),
+            ProtocolVersion::Iso13400_2012
+        );
+        assert_eq!(view.inverse_protocol_version(), 0xfd);
+        assert_eq!(view.payload_type_code(), 0x8001);
+        assert_eq!(view.payload_type(), Some(PayloadType::DiagnosticMessage));
+        assert_eq!(view.payload_length(), 7);
+        assert_eq!(view.payload(), &DIAGNOSTIC_MESSAGE[8..]);
+
+        let diag = view.diagnostic_message().unwrap();
+        assert_eq!(diag.source_address(), [0x0e, 0x00]);
+        assert_eq!(diag.target_address(), [0x10, 0x01]);
+        assert_eq!(diag.user_data(), &[0x22, 0xf1, 0x90]);
+        assert_eq!(diag.user_data().as_ptr(), DIAGNOSTIC_MESSAGE[12..].as_ptr());
+    }
+
+    #[test]
+    fn test_message_view_framing() {
+        let view = DoipMessageView::new(&DIAGNOSTIC_MESSAGE[..14]);
+        assert!(matches!(view, Err(DecodeError::TooShort)));
+
+        let mut bytes = DIAGNOSTIC_MESSAGE.to_vec();
+        bytes.push(0x00);
+        let view = DoipMessageView::new(bytes);
+        assert!(matches!(view, Err(DecodeError::ExceededLength)));
+
+        let view = DoipMessageView::new(&[0x02, 0xff, 0x80, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert!(matches!(view, Err(DecodeError::FailedProtocolValidation)));
+    }
+
+    #[test]
+    fn test_diagnostic_message_view_too_short() {
+        let view = DiagnosticMessageView::new(&[0x0e, 0x00, 0x10][..]);
+        assert!(matches!(view, Err(DecodeError::TooShort)));
+    }
+
+    #[test]
+    fn test_message_view_decode() {
+        let view = DoipMessageView::new(&DIAGNOSTIC_MESSAGE[..]).unwrap();
+        let frame = view.decode(&mut DoipCodec::new()).unwrap();
+
+        assert!(matches!(frame, DoipFrame::Message(_)));
+    }
+
+    #[test]
+    fn test_decode_view() {
+        let mut codec = DoipCodec::new();
+        let mut src = BytesMut::from(&DIAGNOSTIC_MESSAGE[..10]);
+
+        assert!(matches!(codec.decode_view(&mut src), Ok(None)));
+
+        src.extend_from_slice(&DIAGNOSTIC_MESSAGE[10..]);
+        src.extend_from_slice(&DIAGNOSTIC_MESSAGE);
+
+        let view = codec.decode_view(&mut src).unwrap().unwrap();
+        let diag = view.diagnostic_message_bytes().unwrap();
+        assert_eq!(diag.user_data_bytes().as_ref(), &[0x22, 0xf1, 0x90]);
+        assert_eq!(src.len(), DIAGNOSTIC_MESSAGE.len());
+
+        let view = codec.decode_view(&mut src).unwrap().unwrap();
+        assert_eq!(view.as_bytes(), &DIAGNOSTIC_MESSAGE);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_decode_view_message_too_large() {
+        let mut codec = DoipCodec::new().with_max_payload_length(4);
+        let mut src = BytesMut::from(&DIAGNOSTIC_MESSAGE[..]);
+
+        let res = codec.decode_view(&mut src);
+        assert!(matches!(res, Err(DecodeError::MessageTooLarge)));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_decode_view_header_validation() {
+        let mut codec = DoipCodec::new()
+            .with_supported_versions(&[ProtocolVersion::Iso13400_2019])
+            .with_recovery_policy(RecoveryPolicy::SkipFrame);
+        let mut src = BytesMut::from(&DIAGNOSTIC_MESSAGE[..]);
+
+        let res = codec.decode_view(&mut src);
+        assert!(matches!(res, Err(DecodeError::UnsupportedProtocolVersion)));
+        assert!(src.is_empty());
+
+        let mut codec = DoipCodec::new();
+        let mut src = BytesMut::from(&[0x02, 0xfd, 0x90, 0x42, 0x00, 0x00, 0x00, 0x00][..]);
+
+        let res = codec.decode_view(&mut src);
+        assert!(matches!(res, Err(DecodeError::InvalidPayloadType)));
+
+        let mut codec = DoipCodec::new().with_strict_length(true);
+        let mut src = BytesMut::from(&[0x02, 0xfd, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00][..]);
+
+        let res = codec.decode_view(&mut src);
+        assert!(matches!(res, Err(DecodeError::InvalidPayloadLength)));
+    }
+}